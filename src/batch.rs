@@ -0,0 +1,162 @@
+use crate::client::Client;
+use crate::error::p_error;
+use crate::ids::ClientId;
+use crate::rules::{ClientRuleState, RulesEngine};
+use crate::transaction::Transaction;
+use std::collections::HashMap;
+use std::error::Error;
+use std::str::FromStr;
+
+// Groups of rows that are applied all-or-nothing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Atomicity {
+    // Every row stands on its own
+    #[default]
+    Row,
+    // Every input file
+    File,
+    // Consecutive rows with the same value in the batch column. Rows
+    // without a batch id stand on their own.
+    Batch,
+}
+
+impl FromStr for Atomicity {
+    type Err = Box<dyn Error>;
+
+    fn from_str(atomicity: &str) -> Result<Self, Self::Err> {
+        match atomicity {
+            "row" => Ok(Atomicity::Row),
+            "file" => Ok(Atomicity::File),
+            "batch" => Ok(Atomicity::Batch),
+            _ => p_error(format!(
+                "Unknown atomicity {}. Use row, file or batch.",
+                atomicity
+            )),
+        }
+    }
+}
+
+// Processor state outside of the clients when a group started. Lists
+// that only grow are kept as their length.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub rows_processed: u64,
+    pub latest_timestamp: Option<u64>,
+    pub synthetic_events: usize,
+    pub alerts: usize,
+}
+
+/*******************************
+< Batch >
+
+Undo log of an open group of rows. A client, and what the rules remember
+about it, is copied the first time the group touches it, so a rollback
+only restores the clients the group changed. Clients the group created
+are removed again.
+
+*******************************/
+#[derive(Debug, Clone)]
+pub struct Batch {
+    // Rejected rows and broken input files of the run when the group started
+    pub rejected: u64,
+    pub read_errors: u64,
+    // Rows the deduplicator accepted during the group
+    pub rows: Vec<Transaction>,
+    // State before the group touched it
+    clients: HashMap<ClientId, (Option<Client>, ClientRuleState)>,
+    snapshot: Snapshot,
+}
+
+impl Batch {
+    pub fn new(rejected: u64, read_errors: u64, snapshot: Snapshot) -> Self {
+        Self {
+            rejected,
+            read_errors,
+            rows: Vec::new(),
+            clients: HashMap::new(),
+            snapshot,
+        }
+    }
+
+    // Copies the client before its first change in the group
+    pub fn save_client(
+        &mut self,
+        client_id: ClientId,
+        clients: &HashMap<ClientId, Client>,
+        rules: &RulesEngine,
+    ) {
+        self.clients.entry(client_id).or_insert_with(|| {
+            (
+                clients.get(&client_id).cloned(),
+                rules.get_client_state(client_id),
+            )
+        });
+    }
+
+    // Puts the saved clients and their rule state back, drops the alerts
+    // raised by the group and returns the rest of the state to restore
+    pub fn rollback(
+        self,
+        clients: &mut HashMap<ClientId, Client>,
+        rules: &mut RulesEngine,
+    ) -> Snapshot {
+        for (client_id, (saved, rule_state)) in self.clients {
+            match saved {
+                Some(client) => clients.insert(client_id, client),
+                None => clients.remove(&client_id),
+            };
+            rules.set_client_state(client_id, rule_state);
+        }
+        rules.truncate_alerts(self.snapshot.alerts);
+
+        self.snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::{cid, tid, TxId};
+    use crate::transaction::TxType;
+
+    fn deposit(client: ClientId, tx: TxId, amount: f32) -> Transaction {
+        Transaction {
+            tx_type: TxType::Deposit,
+            client,
+            tx,
+            amount: Some(amount),
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn test_rollback() {
+        let mut clients: HashMap<ClientId, Client> = HashMap::new();
+        let mut existing: Client = Client::new(cid(1));
+        existing
+            .consume_deposit(deposit(cid(1), tid(1), 5_f32))
+            .unwrap();
+        clients.insert(cid(1), existing);
+
+        let mut rules: RulesEngine = RulesEngine::default();
+        let mut batch: Batch = Batch::new(0, 0, Snapshot::default());
+        batch.save_client(cid(1), &clients, &rules);
+        clients
+            .get_mut(&cid(1))
+            .unwrap()
+            .consume_deposit(deposit(cid(1), tid(2), 3_f32))
+            .unwrap();
+        batch.save_client(cid(2), &clients, &rules);
+        clients.insert(cid(2), Client::new(cid(2)));
+        // Only the first copy is kept
+        batch.save_client(cid(1), &clients, &rules);
+
+        rules.record(3, deposit(cid(1), tid(2), 3_f32));
+
+        batch.rollback(&mut clients, &mut rules);
+        assert_eq!(5_f32, clients[&cid(1)].get_available_amount());
+        assert_eq!(ClientRuleState::default(), rules.get_client_state(cid(1)));
+        assert!(clients[&cid(1)].get_transaction(tid(2)).is_none());
+        assert!(!clients.contains_key(&cid(2)));
+    }
+}
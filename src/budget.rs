@@ -0,0 +1,157 @@
+use crate::error::p_error;
+use crate::exit;
+use crate::summary::RunSummary;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+// What happens when a row is rejected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnError {
+    // The row is reported and processing goes on
+    #[default]
+    Continue,
+    // Processing stops at the first rejected row
+    Abort,
+}
+
+impl FromStr for OnError {
+    type Err = Box<dyn Error>;
+
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        match action {
+            "continue" => Ok(OnError::Continue),
+            "abort" => Ok(OnError::Abort),
+            _ => p_error(format!(
+                "Unknown error action {}. Use continue or abort.",
+                action
+            )),
+        }
+    }
+}
+
+// Why processing was stopped before the end of the input
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AbortReason {
+    // A row was rejected with --on-error abort
+    FirstError,
+    // More rows were rejected than --max-errors allows
+    MaxErrors(u64),
+    // The share of rejected rows is above --max-error-rate
+    MaxErrorRate(f64),
+    // The header of an input file lacks mandatory columns or repeats one
+    InvalidHeader,
+}
+
+impl AbortReason {
+    pub fn get_exit_code(self) -> i32 {
+        match self {
+            AbortReason::FirstError => exit::ABORTED_ON_ERROR,
+            AbortReason::MaxErrors(_) => exit::ABORTED_MAX_ERRORS,
+            AbortReason::MaxErrorRate(_) => exit::ABORTED_MAX_ERROR_RATE,
+            AbortReason::InvalidHeader => exit::USAGE_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AbortReason::FirstError => write!(f, "A row was rejected with --on-error abort."),
+            AbortReason::MaxErrors(max_errors) => {
+                write!(f, "More than {} row(s) were rejected.", max_errors)
+            }
+            AbortReason::MaxErrorRate(max_error_rate) => {
+                write!(f, "More than {} of the rows were rejected.", max_error_rate)
+            }
+            AbortReason::InvalidHeader => write!(f, "An input file has an invalid header."),
+        }
+    }
+}
+
+/*******************************
+< ErrorBudget >
+
+Number and share of rejected rows a run accepts before it's aborted. The
+error count is checked after every rejected row, the error rate once the
+whole input has been read, so a bad first row doesn't abort the run.
+
+*******************************/
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ErrorBudget {
+    pub on_error: OnError,
+    pub max_errors: Option<u64>,
+    // Fraction of the rows read, between 0 and 1
+    pub max_error_rate: Option<f64>,
+}
+
+impl ErrorBudget {
+    pub fn check_errors(&self, summary: &RunSummary) -> Option<AbortReason> {
+        if self.on_error == OnError::Abort && summary.rejected > 0 {
+            return Some(AbortReason::FirstError);
+        }
+
+        match self.max_errors {
+            Some(max_errors) if summary.rejected > max_errors => {
+                Some(AbortReason::MaxErrors(max_errors))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn check_error_rate(&self, summary: &RunSummary) -> Option<AbortReason> {
+        match self.max_error_rate {
+            Some(max_error_rate)
+                if summary.rows > 0
+                    && summary.rejected as f64 / summary.rows as f64 > max_error_rate =>
+            {
+                Some(AbortReason::MaxErrorRate(max_error_rate))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(rows: u64, rejected: u64) -> RunSummary {
+        RunSummary {
+            rows,
+            rejected,
+            ..RunSummary::default()
+        }
+    }
+
+    #[test]
+    fn test_error_budget() {
+        let budget: ErrorBudget = ErrorBudget {
+            on_error: OnError::Continue,
+            max_errors: Some(2),
+            max_error_rate: Some(0.25),
+        };
+
+        assert_eq!(None, budget.check_errors(&summary(3, 2)));
+        assert_eq!(
+            Some(AbortReason::MaxErrors(2)),
+            budget.check_errors(&summary(3, 3))
+        );
+        assert_eq!(None, budget.check_error_rate(&summary(8, 2)));
+        assert_eq!(
+            Some(AbortReason::MaxErrorRate(0.25)),
+            budget.check_error_rate(&summary(7, 2))
+        );
+        assert_eq!(None, budget.check_error_rate(&summary(0, 0)));
+
+        let abort: ErrorBudget = ErrorBudget {
+            on_error: OnError::Abort,
+            ..ErrorBudget::default()
+        };
+        assert_eq!(None, abort.check_errors(&summary(5, 0)));
+        assert_eq!(
+            Some(AbortReason::FirstError),
+            abort.check_errors(&summary(5, 1))
+        );
+    }
+}
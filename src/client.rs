@@ -1,39 +1,141 @@
 use crate::error::p_error;
+use crate::ids::{ClientId, TxId};
+use crate::ledger::{from_minor_units, to_minor_units, Account, Ledger};
+use crate::policy::PolicyKind;
 use crate::transaction::{Transaction, TxType};
+use serde::Deserialize;
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::fmt;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientStatus {
+    #[default]
+    Active,
+    // Frozen accounts don't accept withdrawals and authorizations
+    Frozen,
+    // Closed accounts don't accept any transaction
+    Closed,
+}
+
+impl fmt::Display for ClientStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientStatus::Active => write!(f, "active"),
+            ClientStatus::Frozen => write!(f, "frozen"),
+            ClientStatus::Closed => write!(f, "closed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockReason {
+    // Account was already locked when opened
+    OpeningBalance,
+    // A transaction of the account was charged back
+    Chargeback,
+    // A lock policy threshold was exceeded
+    Policy(PolicyKind),
+}
+
+impl fmt::Display for LockReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LockReason::OpeningBalance => write!(f, "opening_balance"),
+            LockReason::Chargeback => write!(f, "chargeback"),
+            LockReason::Policy(policy) => write!(f, "{}", policy),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Client {
-    id: u16,
+    id: ClientId,
+    // Master data, only known for clients given in the client registry
+    name: Option<String>,
+    status: ClientStatus,
+    // Balances the account was opened with, e.g. when migrated from
+    // a legacy system
+    opening_available: f32,
+    opening_held: f32,
+    opening_locked: bool,
     available_amount: f32,
     held_amount: f32,
     locked: bool,
-    transactions: HashMap<u32, Transaction>,
-    disputed_transactions: HashSet<u32>,
+    // Why the account got locked, the first reason is kept
+    lock_reason: Option<LockReason>,
+    transactions: HashMap<TxId, Transaction>,
+    disputed_transactions: HashSet<TxId>,
     // Transactions that went through dispute -> resolve are
     // put here, so they can't be re-disputed and re-resolved/re-chargedback again
-    resolved_transactions: HashSet<u32>,
+    resolved_transactions: HashSet<TxId>,
+    // Chargebacks, keyed by the transaction they charged back. Their keys
+    // are the subset of resolved transactions that ended with a chargeback
+    chargebacks: HashMap<TxId, Transaction>,
+    // Lock policy that fired for the account, recorded apart from the
+    // lock state so audits can recompute it
+    policy_lock: Option<PolicyKind>,
+    // Reversals, keyed by the transaction they undid
+    reversed_transactions: HashMap<TxId, Transaction>,
+    // Authorizations whose funds are still held
+    open_authorizations: HashSet<TxId>,
+    // Captures, keyed by the authorization they closed, with the amount
+    // that was debited
+    captured_transactions: HashMap<TxId, Transaction>,
+    // Risk counters, used by the lock policies
+    deposit_count: u32,
+    dispute_count: u32,
+    // Withdrawals may take available funds down to -credit_limit
+    credit_limit: f32,
+    // Deepest overdraft reached so far. No interest is charged on it
+    peak_overdraft: f32,
+    // Double-entry journal of every balance movement on this account
+    ledger: Ledger,
 }
 
 impl Client {
-    pub fn new(client_id: u16) -> Self {
+    pub fn new(client_id: ClientId) -> Self {
         Self {
             id: client_id,
+            name: None,
+            status: ClientStatus::Active,
+            opening_available: 0_f32,
+            opening_held: 0_f32,
+            opening_locked: false,
             available_amount: 0_f32,
             held_amount: 0_f32,
             locked: false,
+            lock_reason: None,
             transactions: HashMap::new(),
             disputed_transactions: HashSet::new(),
             resolved_transactions: HashSet::new(),
+            chargebacks: HashMap::new(),
+            policy_lock: None,
+            reversed_transactions: HashMap::new(),
+            open_authorizations: HashSet::new(),
+            captured_transactions: HashMap::new(),
+            deposit_count: 0,
+            dispute_count: 0,
+            credit_limit: 0_f32,
+            peak_overdraft: 0_f32,
+            ledger: Ledger::new(),
         }
     }
 
     // Getters
-    pub fn get_id(&self) -> u16 {
+    pub fn get_id(&self) -> ClientId {
         self.id
     }
 
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn get_status(&self) -> ClientStatus {
+        self.status
+    }
+
     pub fn get_available_amount(&self) -> f32 {
         self.available_amount
     }
@@ -46,33 +148,100 @@ impl Client {
         self.held_amount + self.available_amount
     }
 
+    pub fn get_deposit_count(&self) -> u32 {
+        self.deposit_count
+    }
+
+    pub fn get_dispute_count(&self) -> u32 {
+        self.dispute_count
+    }
+
+    pub fn get_chargeback_count(&self) -> u32 {
+        self.chargebacks.len() as u32
+    }
+
+    pub fn get_open_dispute_count(&self) -> u32 {
+        self.disputed_transactions.len() as u32
+    }
+
+    // Ratios are relative to deposits. Clients without deposits are
+    // counted as having a single one.
+    pub fn get_dispute_ratio(&self) -> f32 {
+        self.dispute_count as f32 / self.deposit_count.max(1) as f32
+    }
+
+    pub fn get_chargeback_ratio(&self) -> f32 {
+        self.get_chargeback_count() as f32 / self.deposit_count.max(1) as f32
+    }
+
+    pub fn get_credit_limit(&self) -> f32 {
+        self.credit_limit
+    }
+
+    // Amount owed to the bank, 0 if available funds are not negative
+    pub fn get_overdraft_amount(&self) -> f32 {
+        (-self.available_amount).max(0_f32)
+    }
+
+    pub fn get_peak_overdraft(&self) -> f32 {
+        self.peak_overdraft
+    }
+
+    pub fn get_ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
     // Transaction helper functions
     pub fn add_transaction(&mut self, transaction: Transaction) {
         self.transactions
             .insert(transaction.get_tx_id(), transaction);
     }
 
-    pub fn get_transaction(&self, transaction_id: u32) -> Option<&Transaction> {
+    pub fn get_transaction(&self, transaction_id: TxId) -> Option<&Transaction> {
         self.transactions.get(&transaction_id)
     }
 
     // Disputed transactions helper functions
-    pub fn add_disputed_transaction(&mut self, transaction_id: u32) -> bool {
+    pub fn add_disputed_transaction(&mut self, transaction_id: TxId) -> bool {
         self.disputed_transactions.insert(transaction_id)
     }
 
-    pub fn remove_disputed_transaction(&mut self, transaction_id: u32) -> bool {
+    pub fn remove_disputed_transaction(&mut self, transaction_id: TxId) -> bool {
         self.disputed_transactions.remove(&transaction_id)
     }
 
-    pub fn check_disputed_transaction(&self, transaction_id: u32) -> bool {
+    pub fn check_disputed_transaction(&self, transaction_id: TxId) -> bool {
         self.disputed_transactions.contains(&transaction_id)
     }
 
-    pub fn check_resolved_transaction(&self, transaction_id: u32) -> bool {
+    pub fn check_resolved_transaction(&self, transaction_id: TxId) -> bool {
         self.resolved_transactions.contains(&transaction_id)
     }
 
+    pub fn check_chargedback_transaction(&self, transaction_id: TxId) -> bool {
+        self.chargebacks.contains_key(&transaction_id)
+    }
+
+    pub fn check_reversed_transaction(&self, transaction_id: TxId) -> bool {
+        self.reversed_transactions.contains_key(&transaction_id)
+    }
+
+    pub fn get_reversal(&self, transaction_id: TxId) -> Option<&Transaction> {
+        self.reversed_transactions.get(&transaction_id)
+    }
+
+    pub fn check_open_authorization(&self, transaction_id: TxId) -> bool {
+        self.open_authorizations.contains(&transaction_id)
+    }
+
+    pub fn get_capture(&self, transaction_id: TxId) -> Option<&Transaction> {
+        self.captured_transactions.get(&transaction_id)
+    }
+
+    pub fn check_locked_account(&self) -> bool {
+        self.locked
+    }
+
     // Amount helper functions
     pub fn increase_available_amount(&mut self, amount: f32) {
         self.available_amount += amount;
@@ -80,6 +249,7 @@ impl Client {
 
     pub fn decrease_available_amount(&mut self, amount: f32) {
         self.available_amount -= amount;
+        self.peak_overdraft = self.peak_overdraft.max(self.get_overdraft_amount());
     }
 
     pub fn increase_held_amount(&mut self, amount: f32) {
@@ -90,23 +260,141 @@ impl Client {
         self.held_amount -= amount;
     }
 
+    // Master data helpers
+    pub fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    pub fn set_status(&mut self, status: ClientStatus) {
+        self.status = status;
+    }
+
+    // Opens the account with initial balances, booked against the
+    // opening balance account of the bank. Opening held funds don't
+    // belong to any disputed transaction, so they stay held.
+    pub fn open_account(
+        &mut self,
+        available: f32,
+        held: f32,
+        locked: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        if !self.transactions.is_empty()
+            || self.opening_available != 0_f32
+            || self.opening_held != 0_f32
+            || self.opening_locked
+        {
+            return p_error(format!(
+                "Client {} already has a balance and can't be opened again.",
+                self.id
+            ));
+        }
+        if held < 0_f32 {
+            return p_error(format!(
+                "Client {} cannot be opened with a negative held amount.",
+                self.id
+            ));
+        }
+
+        let minor_available: i64 = to_minor_units(available);
+        let minor_held: i64 = to_minor_units(held);
+        self.ledger.post(
+            None,
+            &[
+                (Account::ClientAvailable(self.id), minor_available),
+                (Account::ClientHeld(self.id), minor_held),
+                (Account::OpeningBalance, -minor_available - minor_held),
+            ],
+        )?;
+
+        self.opening_available = available;
+        self.opening_held = held;
+        self.opening_locked = locked;
+        self.increase_available_amount(available);
+        self.increase_held_amount(held);
+        if locked {
+            self.lock_account_for(LockReason::OpeningBalance);
+        }
+
+        Ok(())
+    }
+
+    // Replaces the balances the account was opened with, before any
+    // transaction. The previous opening balances are reversed in the ledger.
+    pub fn reopen_account(
+        &mut self,
+        available: f32,
+        held: f32,
+        locked: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        if !self.transactions.is_empty() {
+            return p_error(format!(
+                "Client {} already has transactions and can't be opened again.",
+                self.id
+            ));
+        }
+
+        if self.opening_available != 0_f32 || self.opening_held != 0_f32 {
+            let minor_available: i64 = to_minor_units(self.opening_available);
+            let minor_held: i64 = to_minor_units(self.opening_held);
+            self.ledger.post(
+                None,
+                &[
+                    (Account::ClientAvailable(self.id), -minor_available),
+                    (Account::ClientHeld(self.id), -minor_held),
+                    (Account::OpeningBalance, minor_available + minor_held),
+                ],
+            )?;
+
+            self.decrease_available_amount(self.opening_available);
+            self.decrease_held_amount(self.opening_held);
+            self.opening_available = 0_f32;
+            self.opening_held = 0_f32;
+        }
+        if self.opening_locked {
+            self.opening_locked = false;
+            self.locked = false;
+            self.lock_reason = None;
+        }
+
+        self.open_account(available, held, locked)
+    }
+
+    // Credit line helper
+    pub fn set_credit_limit(&mut self, credit_limit: f32) {
+        self.credit_limit = credit_limit;
+    }
+
     // Lock helper
     pub fn lock_account(&mut self, lock: bool) {
         self.locked = lock;
     }
 
+    pub fn lock_account_for(&mut self, reason: LockReason) {
+        if !self.locked {
+            self.lock_reason = Some(reason);
+        }
+        self.locked = true;
+    }
+
+    pub fn lock_account_for_policy(&mut self, policy: PolicyKind) {
+        self.policy_lock.get_or_insert(policy);
+        self.lock_account_for(LockReason::Policy(policy));
+    }
+
     pub fn is_locked(&self) -> bool {
         self.locked
     }
 
+    pub fn get_lock_reason(&self) -> Option<LockReason> {
+        self.lock_reason
+    }
+
     // Transaction consumers
     pub fn consume_deposit(&mut self, transaction: Transaction) -> Result<(), Box<dyn Error>> {
         if transaction.get_tx_type() != TxType::Deposit {
-            return p_error(format!(
-                "Deposit consumer accepts only DEPOSIT type transactions."
-            ));
+            return p_error("Deposit consumer accepts only DEPOSIT type transactions.".to_string());
         }
-        let tx_id: u32 = transaction.get_tx_id();
+        let tx_id: TxId = transaction.get_tx_id();
         let amount: f32 = transaction.get_amount();
 
         // Transaction amount has to be bigger than 0
@@ -124,19 +412,29 @@ impl Client {
             ));
         }
 
-        self.increase_available_amount(transaction.get_amount());
+        let minor_amount: i64 = to_minor_units(amount);
+        self.ledger.post(
+            Some(tx_id),
+            &[
+                (Account::ClientAvailable(self.id), minor_amount),
+                (Account::BankClearing, -minor_amount),
+            ],
+        )?;
+
+        self.increase_available_amount(amount);
         self.add_transaction(transaction);
+        self.deposit_count += 1;
 
         Ok(())
     }
 
     pub fn consume_withdrawal(&mut self, transaction: Transaction) -> Result<(), Box<dyn Error>> {
         if transaction.get_tx_type() != TxType::Withdrawal {
-            return p_error(format!(
-                "Withdrawal consumer accepts only WITHDRAWAL type transactions."
-            ));
+            return p_error(
+                "Withdrawal consumer accepts only WITHDRAWAL type transactions.".to_string(),
+            );
         }
-        let tx_id: u32 = transaction.get_tx_id();
+        let tx_id: TxId = transaction.get_tx_id();
         let amount: f32 = transaction.get_amount();
 
         // Transaction amount has to be bigger than 0
@@ -156,16 +454,25 @@ impl Client {
 
         // Locked accounts do not accept withdrawals
         if self.is_locked() {
-            return p_error(format!("Locked accounts cannot accept withdrawals."));
+            return p_error("Locked accounts cannot accept withdrawals.".to_string());
         }
 
-        // Tx amount has to be bigger than available amount
-        if self.get_available_amount() < amount {
+        // Tx amount can't exceed available amount plus the credit line
+        if self.get_available_amount() + self.get_credit_limit() < amount {
             return p_error(format!(
                 "Invalid withdrawal transaction {}. Available amount is smaller than withdraw amount.", tx_id
             ));
         }
 
+        let minor_amount: i64 = to_minor_units(amount);
+        self.ledger.post(
+            Some(tx_id),
+            &[
+                (Account::ClientAvailable(self.id), -minor_amount),
+                (Account::BankClearing, minor_amount),
+            ],
+        )?;
+
         self.decrease_available_amount(amount);
         self.add_transaction(transaction);
 
@@ -174,31 +481,45 @@ impl Client {
 
     pub fn consume_dispute(&mut self, transaction: Transaction) -> Result<(), Box<dyn Error>> {
         if transaction.get_tx_type() != TxType::Dispute {
+            return p_error("Dispute consumer accepts only DISPUTE type transactions.".to_string());
+        }
+        let tx_id: TxId = transaction.get_tx_id();
+
+        // Reversed transactions no longer move any funds
+        if self.check_reversed_transaction(tx_id) {
             return p_error(format!(
-                "Dispute consumer accepts only DISPUTE type transactions."
+                "Transaction {} was reversed and can't be disputed.",
+                tx_id
             ));
         }
-        let tx_id: u32 = transaction.get_tx_id();
 
         // Transaction can't be already disputed or resolved
-        if self.check_disputed_transaction(tx_id) == false
-            && self.check_resolved_transaction(tx_id) == false
-        {
+        if !self.check_disputed_transaction(tx_id) && !self.check_resolved_transaction(tx_id) {
             if let Some(tx) = self.get_transaction(tx_id) {
                 match tx.get_tx_type() {
                     TxType::Deposit => {
                         let disputed_amount: f32 = tx.get_amount();
-                        self.held_amount += disputed_amount;
-                        self.available_amount -= disputed_amount;
+                        let minor_amount: i64 = to_minor_units(disputed_amount);
+                        self.ledger.post(
+                            Some(tx_id),
+                            &[
+                                (Account::ClientAvailable(self.id), -minor_amount),
+                                (Account::ClientHeld(self.id), minor_amount),
+                            ],
+                        )?;
+                        self.increase_held_amount(disputed_amount);
+                        self.decrease_available_amount(disputed_amount);
                         self.disputed_transactions.insert(tx_id);
+                        self.dispute_count += 1;
                     }
                     TxType::Withdrawal => {
                         self.disputed_transactions.insert(tx_id);
+                        self.dispute_count += 1;
                     }
                     _ => {
-                        return p_error(format!(
-                            "Only DEPOSIT and WITHDRAWAL transactions can be disputed."
-                        ))
+                        return p_error(
+                            "Only DEPOSIT and WITHDRAWAL transactions can be disputed.".to_string(),
+                        )
                     }
                 }
             } else {
@@ -219,18 +540,24 @@ impl Client {
 
     pub fn consume_resolve(&mut self, transaction: Transaction) -> Result<(), Box<dyn Error>> {
         if transaction.get_tx_type() != TxType::Resolve {
-            return p_error(format!(
-                "Resolve consumer accepts only RESOLVE type transactions."
-            ));
+            return p_error("Resolve consumer accepts only RESOLVE type transactions.".to_string());
         }
-        let tx_id: u32 = transaction.get_tx_id();
+        let tx_id: TxId = transaction.get_tx_id();
 
         // Transaction has to be disputed in order to be resolved
-        if let true = self.check_disputed_transaction(tx_id) {
+        if self.check_disputed_transaction(tx_id) {
             if let Some(tx) = self.get_transaction(tx_id) {
                 match tx.get_tx_type() {
                     TxType::Deposit => {
                         let disputed_amount = tx.get_amount();
+                        let minor_amount: i64 = to_minor_units(disputed_amount);
+                        self.ledger.post(
+                            Some(tx_id),
+                            &[
+                                (Account::ClientHeld(self.id), -minor_amount),
+                                (Account::ClientAvailable(self.id), minor_amount),
+                            ],
+                        )?;
                         self.held_amount -= disputed_amount;
                         self.available_amount += disputed_amount;
                         self.disputed_transactions.remove(&tx_id);
@@ -241,9 +568,9 @@ impl Client {
                         self.resolved_transactions.insert(tx_id);
                     }
                     _ => {
-                        return p_error(format!(
-                            "Only DEPOSIT and WITHDRAWAL transactions can be resolved."
-                        ))
+                        return p_error(
+                            "Only DEPOSIT and WITHDRAWAL transactions can be resolved.".to_string(),
+                        )
                     }
                 }
             } else {
@@ -261,38 +588,60 @@ impl Client {
 
     pub fn consume_chargeback(&mut self, transaction: Transaction) -> Result<(), Box<dyn Error>> {
         if transaction.get_tx_type() != TxType::Chargeback {
-            return p_error(format!(
-                "Chargeback consumer accepts only CHARGEBACK type transactions."
-            ));
+            return p_error(
+                "Chargeback consumer accepts only CHARGEBACK type transactions.".to_string(),
+            );
         }
-        let tx_id: u32 = transaction.get_tx_id();
+        let tx_id: TxId = transaction.get_tx_id();
 
         // Transaction has to be disputed in order to be charged back
-        if let true = self.check_disputed_transaction(tx_id) {
+        if self.check_disputed_transaction(tx_id) {
             if let Some(tx) = self.get_transaction(tx_id) {
                 match tx.get_tx_type() {
                     TxType::Deposit => {
+                        // Held funds are returned to the payer through
+                        // the clearing account
                         let disputed_amount = tx.get_amount();
+                        let minor_amount: i64 = to_minor_units(disputed_amount);
+                        self.ledger.post(
+                            Some(tx_id),
+                            &[
+                                (Account::ClientHeld(self.id), -minor_amount),
+                                (Account::BankClearing, minor_amount),
+                            ],
+                        )?;
                         self.held_amount -= disputed_amount;
                         self.disputed_transactions.remove(&tx_id);
-                        self.lock_account(true);
+                        self.lock_account_for(LockReason::Chargeback);
                         self.resolved_transactions.insert(tx_id);
+                        self.chargebacks.insert(tx_id, transaction);
                     }
                     // Chargebacks for withdrawals mean adding the amount
                     // back to the client account, then locking the account
                     // to prevent further malicious actions. More details
                     // in the README.md
                     TxType::Withdrawal => {
+                        // The refund is a loss absorbed by the bank
                         let disputed_amount = tx.get_amount();
+                        let minor_amount: i64 = to_minor_units(disputed_amount);
+                        self.ledger.post(
+                            Some(tx_id),
+                            &[
+                                (Account::ClientAvailable(self.id), minor_amount),
+                                (Account::ChargebackLoss, -minor_amount),
+                            ],
+                        )?;
                         self.available_amount += disputed_amount;
                         self.disputed_transactions.remove(&tx_id);
-                        self.lock_account(true);
+                        self.lock_account_for(LockReason::Chargeback);
                         self.resolved_transactions.insert(tx_id);
+                        self.chargebacks.insert(tx_id, transaction);
                     }
                     _ => {
-                        return p_error(format!(
+                        return p_error(
                             "Only DEPOSIT and WITHDRAWAL transactions can be chargedback."
-                        ))
+                                .to_string(),
+                        )
                     }
                 }
             } else {
@@ -308,6 +657,411 @@ impl Client {
         Ok(())
     }
 
+    // Reversals are operator corrections done by the bank. The balance
+    // effect of the original transaction is undone even if that takes
+    // available funds below the credit limit, and the account isn't locked.
+    pub fn consume_reversal(&mut self, transaction: Transaction) -> Result<(), Box<dyn Error>> {
+        if transaction.get_tx_type() != TxType::Reversal {
+            return p_error(
+                "Reversal consumer accepts only REVERSAL type transactions.".to_string(),
+            );
+        }
+        let tx_id: TxId = transaction.get_tx_id();
+
+        if self.check_reversed_transaction(tx_id) {
+            return p_error(format!("Transaction {} is already reversed.", tx_id));
+        }
+        // Disputed funds are held and chargedback ones are already returned
+        if self.check_disputed_transaction(tx_id) || self.check_chargedback_transaction(tx_id) {
+            return p_error(format!(
+                "Transaction {} is disputed/chargedback and can't be reversed.",
+                tx_id
+            ));
+        }
+
+        let tx: Transaction = match self.get_transaction(tx_id) {
+            Some(tx) => *tx,
+            None => {
+                return p_error(format!(
+                    "Transaction {} isn't registered for client {}.",
+                    tx_id, self.id
+                ))
+            }
+        };
+        let amount: f32 = tx.get_amount();
+        let minor_amount: i64 = to_minor_units(amount);
+        match tx.get_tx_type() {
+            TxType::Deposit => {
+                self.ledger.post(
+                    Some(tx_id),
+                    &[
+                        (Account::ClientAvailable(self.id), -minor_amount),
+                        (Account::BankClearing, minor_amount),
+                    ],
+                )?;
+                self.decrease_available_amount(amount);
+            }
+            TxType::Withdrawal => {
+                self.ledger.post(
+                    Some(tx_id),
+                    &[
+                        (Account::ClientAvailable(self.id), minor_amount),
+                        (Account::BankClearing, -minor_amount),
+                    ],
+                )?;
+                self.increase_available_amount(amount);
+            }
+            _ => {
+                return p_error(
+                    "Only DEPOSIT and WITHDRAWAL transactions can be reversed.".to_string(),
+                )
+            }
+        }
+        self.reversed_transactions.insert(tx_id, transaction);
+
+        Ok(())
+    }
+
+    // Authorizations move funds from available to held until they are
+    // captured, voided or expire
+    pub fn consume_authorize(&mut self, transaction: Transaction) -> Result<(), Box<dyn Error>> {
+        if transaction.get_tx_type() != TxType::Authorize {
+            return p_error(
+                "Authorize consumer accepts only AUTHORIZE type transactions.".to_string(),
+            );
+        }
+        let tx_id: TxId = transaction.get_tx_id();
+        let amount: f32 = transaction.get_amount();
+
+        // Transaction amount has to be bigger than 0
+        if amount <= 0_f32 {
+            return p_error(format!(
+                "Transaction with ID: {} cannot have negative or 0 amount.",
+                transaction.get_tx_id()
+            ));
+        }
+        // Transaction ID should be unique
+        if self.transactions.contains_key(&tx_id) {
+            return p_error(format!(
+                "Transaction with ID: {} already exists.",
+                transaction.get_tx_id()
+            ));
+        }
+
+        // Locked accounts do not accept authorizations
+        if self.is_locked() {
+            return p_error("Locked accounts cannot accept authorizations.".to_string());
+        }
+
+        // Held amount can't exceed available amount plus the credit line
+        if self.get_available_amount() + self.get_credit_limit() < amount {
+            return p_error(format!(
+                "Invalid authorize transaction {}. Available amount is smaller than authorized amount.", tx_id
+            ));
+        }
+
+        let minor_amount: i64 = to_minor_units(amount);
+        self.ledger.post(
+            Some(tx_id),
+            &[
+                (Account::ClientAvailable(self.id), -minor_amount),
+                (Account::ClientHeld(self.id), minor_amount),
+            ],
+        )?;
+
+        self.decrease_available_amount(amount);
+        self.increase_held_amount(amount);
+        self.add_transaction(transaction);
+        self.open_authorizations.insert(tx_id);
+
+        Ok(())
+    }
+
+    // A capture closes the authorization. Without an amount the whole
+    // authorization is debited, otherwise the rest of the hold is released.
+    pub fn consume_capture(&mut self, transaction: Transaction) -> Result<(), Box<dyn Error>> {
+        if transaction.get_tx_type() != TxType::Capture {
+            return p_error("Capture consumer accepts only CAPTURE type transactions.".to_string());
+        }
+        let tx_id: TxId = transaction.get_tx_id();
+        let authorized_amount: f32 = self.get_open_authorization(tx_id, "captured")?;
+        let amount: f32 = match transaction.amount {
+            None => authorized_amount,
+            // Transaction amount has to be bigger than 0
+            Some(amount) if amount <= 0_f32 => {
+                return p_error(format!(
+                    "Transaction with ID: {} cannot have negative or 0 amount.",
+                    tx_id
+                ))
+            }
+            Some(amount) if amount > authorized_amount => {
+                return p_error(format!(
+                    "Invalid capture transaction {}. Authorized amount is smaller than captured amount.",
+                    tx_id
+                ))
+            }
+            Some(amount) => amount,
+        };
+
+        let minor_authorized: i64 = to_minor_units(authorized_amount);
+        let minor_amount: i64 = to_minor_units(amount);
+        self.ledger.post(
+            Some(tx_id),
+            &[
+                (Account::ClientHeld(self.id), -minor_authorized),
+                (Account::BankClearing, minor_amount),
+                (
+                    Account::ClientAvailable(self.id),
+                    minor_authorized - minor_amount,
+                ),
+            ],
+        )?;
+
+        self.decrease_held_amount(authorized_amount);
+        self.increase_available_amount(authorized_amount - amount);
+        self.open_authorizations.remove(&tx_id);
+        self.captured_transactions.insert(
+            tx_id,
+            Transaction {
+                amount: Some(amount),
+                ..transaction
+            },
+        );
+
+        Ok(())
+    }
+
+    pub fn consume_void(&mut self, transaction: Transaction) -> Result<(), Box<dyn Error>> {
+        if transaction.get_tx_type() != TxType::Void {
+            return p_error("Void consumer accepts only VOID type transactions.".to_string());
+        }
+        let tx_id: TxId = transaction.get_tx_id();
+        let authorized_amount: f32 = self.get_open_authorization(tx_id, "voided")?;
+
+        let minor_amount: i64 = to_minor_units(authorized_amount);
+        self.ledger.post(
+            Some(tx_id),
+            &[
+                (Account::ClientHeld(self.id), -minor_amount),
+                (Account::ClientAvailable(self.id), minor_amount),
+            ],
+        )?;
+
+        self.decrease_held_amount(authorized_amount);
+        self.increase_available_amount(authorized_amount);
+        self.open_authorizations.remove(&tx_id);
+
+        Ok(())
+    }
+
+    // Amount held by an authorization that is still open
+    fn get_open_authorization(&self, tx_id: TxId, action: &str) -> Result<f32, Box<dyn Error>> {
+        let tx: &Transaction = match self.get_transaction(tx_id) {
+            Some(tx) => tx,
+            None => {
+                return p_error(format!(
+                    "Transaction {} isn't registered for client {}.",
+                    tx_id, self.id
+                ))
+            }
+        };
+        if tx.get_tx_type() != TxType::Authorize {
+            return p_error(format!("Only AUTHORIZE transactions can be {}.", action));
+        }
+        if !self.check_open_authorization(tx_id) {
+            return p_error(format!(
+                "Authorization {} is already captured/voided.",
+                tx_id
+            ));
+        }
+
+        Ok(tx.get_amount())
+    }
+
+    // Recompute balances from scratch, out of the stored transactions and
+    // their dispute states, and compare them with the incrementally
+    // maintained ones. Returns a description of every mismatch found.
+    pub fn audit(&self) -> Vec<String> {
+        let mut issues: Vec<String> = Vec::new();
+        let mut available: i64 = to_minor_units(self.opening_available);
+        let mut held: i64 = to_minor_units(self.opening_held);
+
+        for (tx_id, tx) in self.transactions.iter() {
+            let amount: i64 = to_minor_units(tx.get_amount());
+            let disputed: bool = self.check_disputed_transaction(*tx_id);
+            let chargedback: bool = self.check_chargedback_transaction(*tx_id);
+            let reversed: bool = self.check_reversed_transaction(*tx_id);
+
+            match tx.get_tx_type() {
+                TxType::Deposit => {
+                    if disputed {
+                        held += amount;
+                    } else if !chargedback && !reversed {
+                        available += amount;
+                    }
+                }
+                TxType::Withdrawal => {
+                    if !chargedback && !reversed {
+                        available -= amount;
+                    }
+                }
+                TxType::Authorize => {
+                    if self.check_open_authorization(*tx_id) {
+                        available -= amount;
+                        held += amount;
+                    } else if let Some(capture) = self.get_capture(*tx_id) {
+                        available -= to_minor_units(capture.get_amount());
+                    }
+                }
+                _ => issues.push(format!(
+                    "Client {}: transaction {} has type {:?}, which can't be stored.",
+                    self.id,
+                    tx_id,
+                    tx.get_tx_type()
+                )),
+            }
+        }
+
+        // Balances are kept as f32, so allow a difference of one minor unit
+        if (available - to_minor_units(self.available_amount)).abs() > 1 {
+            issues.push(format!(
+                "Client {}: available amount is {:.4}, recomputed {:.4}.",
+                self.id,
+                self.available_amount,
+                from_minor_units(available)
+            ));
+        }
+        if (held - to_minor_units(self.held_amount)).abs() > 1 {
+            issues.push(format!(
+                "Client {}: held amount is {:.4}, recomputed {:.4}.",
+                self.id,
+                self.held_amount,
+                from_minor_units(held)
+            ));
+        }
+        // Accounts are locked when opened locked, by any chargeback and
+        // by a lock policy
+        let locked: bool =
+            self.opening_locked || !self.chargebacks.is_empty() || self.policy_lock.is_some();
+        if self.locked != locked {
+            issues.push(format!(
+                "Client {}: account locked is {}, recomputed {}.",
+                self.id, self.locked, locked
+            ));
+        }
+
+        // Invariants
+        if self.held_amount < 0_f32 {
+            issues.push(format!(
+                "Client {}: held amount {:.4} is negative.",
+                self.id, self.held_amount
+            ));
+        }
+        if (to_minor_units(self.get_total_amount())
+            - to_minor_units(self.available_amount)
+            - to_minor_units(self.held_amount))
+        .abs()
+            > 1
+        {
+            issues.push(format!(
+                "Client {}: total amount {:.4} differs from available + held.",
+                self.id,
+                self.get_total_amount()
+            ));
+        }
+        for tx_id in self
+            .disputed_transactions
+            .iter()
+            .chain(self.resolved_transactions.iter())
+        {
+            if !self.transactions.contains_key(tx_id) {
+                let state: &str = if self.check_disputed_transaction(*tx_id) {
+                    "disputed"
+                } else if self.check_chargedback_transaction(*tx_id) {
+                    "chargedback"
+                } else {
+                    "resolved"
+                };
+                issues.push(format!(
+                    "Client {}: {} transaction {} is missing from the transaction map.",
+                    self.id, state, tx_id
+                ));
+            }
+        }
+        if available != self.ledger.get_balance(Account::ClientAvailable(self.id))
+            || held != self.ledger.get_balance(Account::ClientHeld(self.id))
+        {
+            issues.push(format!(
+                "Client {}: ledger balances differ from the recomputed ones.",
+                self.id
+            ));
+        }
+
+        issues
+    }
+
+    // Reconcile the client accounts of the ledger with the incrementally
+    // maintained balances. Both are updated by every consumer, but through
+    // separate code paths, so a consumer forgetting either one shows up here.
+    pub fn check_ledger(&self) -> Result<(), Box<dyn Error>> {
+        let available: i64 = self.ledger.get_balance(Account::ClientAvailable(self.id));
+        let held: i64 = self.ledger.get_balance(Account::ClientHeld(self.id));
+
+        // Balances are kept as f32, so allow a difference of one minor unit
+        if (available - to_minor_units(self.available_amount)).abs() > 1
+            || (held - to_minor_units(self.held_amount)).abs() > 1
+            || (available + held - to_minor_units(self.get_total_amount())).abs() > 1
+        {
+            return p_error(format!(
+                "Ledger of client {} is out of balance. Ledger available {:.4}, held {:.4}, account available {:.4}, held {:.4}, total {:.4}.",
+                self.id,
+                from_minor_units(available),
+                from_minor_units(held),
+                self.available_amount,
+                self.held_amount,
+                self.get_total_amount()
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Overdraft report CSV record
+    pub fn overdraft_record(&self) -> csv::ByteRecord {
+        csv::ByteRecord::from(vec![
+            format!("{}", self.id),
+            format!("{:.4}", self.get_available_amount()),
+            format!("{:.4}", self.get_credit_limit()),
+            format!("{:.4}", self.get_overdraft_amount()),
+            format!("{:.4}", self.get_peak_overdraft()),
+        ])
+    }
+
+    // Risk report CSV record
+    pub fn risk_record(&self) -> csv::ByteRecord {
+        csv::ByteRecord::from(vec![
+            format!("{}", self.id),
+            format!("{}", self.deposit_count),
+            format!("{}", self.dispute_count),
+            format!("{}", self.get_chargeback_count()),
+            format!("{}", self.get_open_dispute_count()),
+            format!("{:.4}", self.get_dispute_ratio()),
+            format!("{:.4}", self.get_chargeback_ratio()),
+            self.locked.to_string(),
+            self.lock_reason
+                .map(|reason| reason.to_string())
+                .unwrap_or_default(),
+        ])
+    }
+
+    // Client CSV record, followed by master data
+    pub fn detailed_record(&self) -> csv::ByteRecord {
+        let mut record: csv::ByteRecord = self.record();
+        record.push_field(self.get_name().unwrap_or_default().as_bytes());
+        record.push_field(self.status.to_string().as_bytes());
+        record
+    }
+
     // Client CSV record
     pub fn record(&self) -> csv::ByteRecord {
         csv::ByteRecord::from(vec![
@@ -323,12 +1077,13 @@ impl Client {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ids::{cid, tid};
 
     #[test]
     fn test_new_client() {
-        let client: Client = Client::new(1);
+        let client: Client = Client::new(cid(1));
 
-        assert_eq!(client.get_id(), 1);
+        assert_eq!(client.get_id(), cid(1));
         assert_eq!(client.get_available_amount(), 0_f32);
         assert_eq!(client.get_held_amount(), 0_f32);
         assert_eq!(client.get_total_amount(), 0_f32);
@@ -336,7 +1091,7 @@ mod tests {
 
     #[test]
     fn test_client_amount_operations() {
-        let mut client: Client = Client::new(1);
+        let mut client: Client = Client::new(cid(1));
 
         client.increase_available_amount(4_f32);
         assert_eq!(client.get_available_amount(), 4_f32);
@@ -355,66 +1110,82 @@ mod tests {
 
     #[test]
     fn test_client_tx_operations() {
-        let mut client: Client = Client::new(1);
+        let mut client: Client = Client::new(cid(1));
         let mut deposit_transaction: Transaction = Transaction {
             tx_type: TxType::Deposit,
-            tx: 1,
-            amount: 2_f32,
-            client: 1,
+            tx: tid(1),
+            amount: Some(2_f32),
+            client: cid(1),
+            timestamp: None,
         };
 
         // Verify successful deposit transaction
         assert_eq!((), client.consume_deposit(deposit_transaction).unwrap());
         assert_eq!(2_f32, client.get_available_amount());
-        assert_eq!(deposit_transaction, *client.get_transaction(1).unwrap());
+        assert_eq!(
+            deposit_transaction,
+            *client.get_transaction(tid(1)).unwrap()
+        );
         assert_eq!(2_f32, client.get_total_amount());
 
         let withdraw_transaction: Transaction = Transaction {
             tx_type: TxType::Withdrawal,
-            tx: 2,
-            amount: 1_f32,
-            client: 1,
+            tx: tid(2),
+            amount: Some(1_f32),
+            client: cid(1),
+            timestamp: None,
         };
 
         // Verify successful withdrawal transaction
         assert_eq!((), client.consume_withdrawal(withdraw_transaction).unwrap());
         assert_eq!(1_f32, client.get_available_amount());
-        assert_eq!(withdraw_transaction, *client.get_transaction(2).unwrap());
+        assert_eq!(
+            withdraw_transaction,
+            *client.get_transaction(tid(2)).unwrap()
+        );
         assert_eq!(1_f32, client.get_total_amount());
 
         // Add more deposit transactions, so we can dispute/resolve/chargeback
-        deposit_transaction.tx += 2;
-        deposit_transaction.amount += 4_f32;
+        deposit_transaction.tx = tid(3);
+        deposit_transaction.amount = Some(6_f32);
         assert_eq!((), client.consume_deposit(deposit_transaction).unwrap());
-        assert_eq!(deposit_transaction, *client.get_transaction(3).unwrap());
+        assert_eq!(
+            deposit_transaction,
+            *client.get_transaction(tid(3)).unwrap()
+        );
 
-        deposit_transaction.tx += 1;
+        deposit_transaction.tx = tid(4);
         assert_eq!((), client.consume_deposit(deposit_transaction).unwrap());
-        assert_eq!(deposit_transaction, *client.get_transaction(4).unwrap());
+        assert_eq!(
+            deposit_transaction,
+            *client.get_transaction(tid(4)).unwrap()
+        );
 
         // Dispute DEPOSIT transaction
         let mut dispute_transaction: Transaction = Transaction {
             tx_type: TxType::Dispute,
-            tx: 4,
-            amount: 0_f32,
-            client: 1,
+            tx: tid(4),
+            amount: None,
+            client: cid(1),
+            timestamp: None,
         };
         assert_eq!((), client.consume_dispute(dispute_transaction).unwrap());
-        assert_eq!(true, client.check_disputed_transaction(4));
+        assert!(client.check_disputed_transaction(tid(4)));
         assert_eq!(6_f32, client.get_held_amount());
         assert_eq!(7_f32, client.get_available_amount());
 
         // Resolve DEPOSIT transaction
         let resolve_transaction: Transaction = Transaction {
             tx_type: TxType::Resolve,
-            tx: 4,
-            amount: 0_f32,
-            client: 1,
+            tx: tid(4),
+            amount: None,
+            client: cid(1),
+            timestamp: None,
         };
 
         assert_eq!((), client.consume_resolve(resolve_transaction).unwrap());
-        assert_eq!(true, client.check_resolved_transaction(4));
-        assert_eq!(false, client.check_disputed_transaction(4));
+        assert!(client.check_resolved_transaction(tid(4)));
+        assert!(!client.check_disputed_transaction(tid(4)));
         assert_eq!(13_f32, client.get_available_amount());
         assert_eq!(0_f32, client.get_held_amount());
 
@@ -423,124 +1194,137 @@ mod tests {
         }
 
         // Dispute another DEPOSIT transaction and do a successful chargeback
-        dispute_transaction.tx = 3;
+        dispute_transaction.tx = tid(3);
         assert_eq!((), client.consume_dispute(dispute_transaction).unwrap());
-        assert_eq!(true, client.check_disputed_transaction(3));
+        assert!(client.check_disputed_transaction(tid(3)));
         assert_eq!(6_f32, client.get_held_amount());
         assert_eq!(7_f32, client.get_available_amount());
 
         let chargeback_transaction: Transaction = Transaction {
             tx_type: TxType::Chargeback,
-            tx: 3,
-            amount: 0_f32,
-            client: 1,
+            tx: tid(3),
+            amount: None,
+            client: cid(1),
+            timestamp: None,
         };
 
         assert_eq!(
             (),
             client.consume_chargeback(chargeback_transaction).unwrap()
         );
-        assert_eq!(false, client.check_disputed_transaction(3));
-        assert_eq!(true, client.check_resolved_transaction(3));
+        assert!(!client.check_disputed_transaction(tid(3)));
+        assert!(client.check_resolved_transaction(tid(3)));
         assert_eq!(0_f32, client.get_held_amount());
         assert_eq!(7_f32, client.get_available_amount());
-        assert_eq!(true, client.is_locked());
+        assert!(client.is_locked());
     }
 
     #[test]
     fn test_client_tx_withdrawal() {
-        let mut client: Client = Client::new(1);
+        let mut client: Client = Client::new(cid(1));
         client.increase_available_amount(10_f32);
         let mut withdraw_transaction: Transaction = Transaction {
             tx_type: TxType::Withdrawal,
-            tx: 1,
-            amount: 2_f32,
-            client: 1,
+            tx: tid(1),
+            amount: Some(2_f32),
+            client: cid(1),
+            timestamp: None,
         };
 
         // Verify first successful withdrawal transaction
         assert_eq!((), client.consume_withdrawal(withdraw_transaction).unwrap());
         assert_eq!(8_f32, client.get_available_amount());
-        assert_eq!(withdraw_transaction, *client.get_transaction(1).unwrap());
+        assert_eq!(
+            withdraw_transaction,
+            *client.get_transaction(tid(1)).unwrap()
+        );
         assert_eq!(8_f32, client.get_total_amount());
 
         // Verify second successful withdrawal transaction
-        withdraw_transaction.tx += 1;
+        withdraw_transaction.tx = tid(2);
         assert_eq!((), client.consume_withdrawal(withdraw_transaction).unwrap());
         assert_eq!(6_f32, client.get_available_amount());
-        assert_eq!(withdraw_transaction, *client.get_transaction(2).unwrap());
+        assert_eq!(
+            withdraw_transaction,
+            *client.get_transaction(tid(2)).unwrap()
+        );
         assert_eq!(6_f32, client.get_total_amount());
 
         // Dispute both transactions
         let mut dispute_transaction: Transaction = Transaction {
             tx_type: TxType::Dispute,
-            tx: 1,
-            amount: 0_f32,
-            client: 1,
+            tx: tid(1),
+            amount: None,
+            client: cid(1),
+            timestamp: None,
         };
 
         assert_eq!((), client.consume_dispute(dispute_transaction).unwrap());
-        assert_eq!(true, client.check_disputed_transaction(1));
+        assert!(client.check_disputed_transaction(tid(1)));
         assert_eq!(0_f32, client.get_held_amount());
         assert_eq!(6_f32, client.get_available_amount());
 
-        dispute_transaction.tx += 1;
+        dispute_transaction.tx = tid(2);
         assert_eq!((), client.consume_dispute(dispute_transaction).unwrap());
-        assert_eq!(true, client.check_disputed_transaction(2));
+        assert!(client.check_disputed_transaction(tid(2)));
         assert_eq!(0_f32, client.get_held_amount());
         assert_eq!(6_f32, client.get_available_amount());
 
         // Resolve first transaction
         let resolve_transaction: Transaction = Transaction {
             tx_type: TxType::Resolve,
-            tx: 1,
-            amount: 0_f32,
-            client: 1,
+            tx: tid(1),
+            amount: None,
+            client: cid(1),
+            timestamp: None,
         };
 
         assert_eq!((), client.consume_resolve(resolve_transaction).unwrap());
-        assert_eq!(true, client.check_resolved_transaction(1));
-        assert_eq!(false, client.check_disputed_transaction(1));
+        assert!(client.check_resolved_transaction(tid(1)));
+        assert!(!client.check_disputed_transaction(tid(1)));
         assert_eq!(6_f32, client.get_available_amount());
 
         // Chargeback second transaction
         let chargeback_transaction: Transaction = Transaction {
             tx_type: TxType::Chargeback,
-            tx: 2,
-            amount: 0_f32,
-            client: 1,
+            tx: tid(2),
+            amount: None,
+            client: cid(1),
+            timestamp: None,
         };
 
         assert_eq!(
             (),
             client.consume_chargeback(chargeback_transaction).unwrap()
         );
-        assert_eq!(true, client.check_resolved_transaction(2));
-        assert_eq!(false, client.check_disputed_transaction(2));
+        assert!(client.check_resolved_transaction(tid(2)));
+        assert!(!client.check_disputed_transaction(tid(2)));
         assert_eq!(8_f32, client.get_available_amount());
-        assert_eq!(true, client.is_locked());
+        assert!(client.is_locked());
     }
 
     #[test]
     fn test_tx_errors() {
-        let mut client: Client = Client::new(1);
+        let mut client: Client = Client::new(cid(1));
         let mut deposit_transaction: Transaction = Transaction {
             tx_type: TxType::Deposit,
-            tx: 1,
-            amount: 20_f32,
-            client: 1,
+            tx: tid(1),
+            amount: Some(20_f32),
+            client: cid(1),
+            timestamp: None,
         };
         // Add two transactions
         assert_eq!((), client.consume_deposit(deposit_transaction).unwrap());
-        deposit_transaction.tx += 1;
+        deposit_transaction.tx = tid(2);
         assert_eq!((), client.consume_deposit(deposit_transaction).unwrap());
 
         // Try to withdraw more than available
         let withdrawal_transaction: Transaction = Transaction {
             tx_type: TxType::Withdrawal,
-            tx: 3,
-            amount: 50_f32,
-            client: 1,
+            tx: tid(3),
+            amount: Some(50_f32),
+            client: cid(1),
+            timestamp: None,
         };
         assert_eq!(
             "PROCESSOR ERROR: Invalid withdrawal transaction 3. Available amount is smaller than withdraw amount.",
@@ -561,16 +1345,17 @@ mod tests {
 
         let mut dispute_transaction: Transaction = Transaction {
             tx_type: TxType::Dispute,
-            tx: 1,
-            amount: 0_f32,
-            client: 1,
+            tx: tid(1),
+            amount: None,
+            client: cid(1),
+            timestamp: None,
         };
 
         // Dispute first transaction
         assert_eq!((), client.consume_dispute(dispute_transaction).unwrap());
 
         // Double dispute the second transaction
-        dispute_transaction.tx = 2;
+        dispute_transaction.tx = tid(2);
         assert_eq!((), client.consume_dispute(dispute_transaction).unwrap());
         assert_eq!(
             "PROCESSOR ERROR: Transaction 2 is already disputed/resolved.",
@@ -583,9 +1368,10 @@ mod tests {
         // Resolve second transaction and then try to resolve it again
         let resolve_transaction: Transaction = Transaction {
             tx_type: TxType::Resolve,
-            tx: 2,
-            amount: 0_f32,
-            client: 1,
+            tx: tid(2),
+            amount: None,
+            client: cid(1),
+            timestamp: None,
         };
         assert_eq!((), client.consume_resolve(resolve_transaction).unwrap());
         assert_eq!(
@@ -599,9 +1385,10 @@ mod tests {
         // Chargeback first transaction and then try to chargeback again
         let chargeback_transaction: Transaction = Transaction {
             tx_type: TxType::Chargeback,
-            tx: 1,
-            amount: 0_f32,
-            client: 1,
+            tx: tid(1),
+            amount: None,
+            client: cid(1),
+            timestamp: None,
         };
         assert_eq!(
             (),
@@ -615,4 +1402,467 @@ mod tests {
                 .to_string()
         );
     }
+
+    #[test]
+    fn test_client_ledger_postings() {
+        let mut client: Client = Client::new(cid(1));
+        let mut deposit_transaction: Transaction = Transaction {
+            tx_type: TxType::Deposit,
+            tx: tid(1),
+            amount: Some(10_f32),
+            client: cid(1),
+            timestamp: None,
+        };
+        assert_eq!((), client.consume_deposit(deposit_transaction).unwrap());
+        deposit_transaction.tx = tid(2);
+        assert_eq!((), client.consume_deposit(deposit_transaction).unwrap());
+
+        let withdrawal_transaction: Transaction = Transaction {
+            tx_type: TxType::Withdrawal,
+            tx: tid(3),
+            amount: Some(2.5_f32),
+            client: cid(1),
+            timestamp: None,
+        };
+        assert_eq!(
+            (),
+            client.consume_withdrawal(withdrawal_transaction).unwrap()
+        );
+
+        // Dispute and chargeback the deposit, dispute and chargeback the withdrawal
+        let mut dispute_transaction: Transaction = Transaction {
+            tx_type: TxType::Dispute,
+            tx: tid(1),
+            amount: None,
+            client: cid(1),
+            timestamp: None,
+        };
+        assert_eq!((), client.consume_dispute(dispute_transaction).unwrap());
+        assert_eq!(
+            100000,
+            client.get_ledger().get_balance(Account::ClientHeld(cid(1)))
+        );
+
+        let mut chargeback_transaction: Transaction = Transaction {
+            tx_type: TxType::Chargeback,
+            tx: tid(1),
+            amount: None,
+            client: cid(1),
+            timestamp: None,
+        };
+        assert_eq!(
+            (),
+            client.consume_chargeback(chargeback_transaction).unwrap()
+        );
+
+        dispute_transaction.tx = tid(3);
+        chargeback_transaction.tx = tid(3);
+        assert_eq!((), client.consume_dispute(dispute_transaction).unwrap());
+        assert_eq!(
+            (),
+            client.consume_chargeback(chargeback_transaction).unwrap()
+        );
+
+        let ledger: &Ledger = client.get_ledger();
+        assert_eq!(
+            to_minor_units(client.get_available_amount()),
+            ledger.get_balance(Account::ClientAvailable(cid(1)))
+        );
+        assert_eq!(0, ledger.get_balance(Account::ClientHeld(cid(1))));
+        assert_eq!(-75000, ledger.get_balance(Account::BankClearing));
+        assert_eq!(-25000, ledger.get_balance(Account::ChargebackLoss));
+        assert_eq!((), client.check_ledger().unwrap());
+
+        // A balance change that wasn't posted to the ledger
+        client.increase_available_amount(1_f32);
+        assert_eq!(
+            "PROCESSOR ERROR: Ledger of client 1 is out of balance. Ledger available 10.0000, held 0.0000, account available 11.0000, held 0.0000, total 11.0000.",
+            client.check_ledger().unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_client_audit() {
+        let mut client: Client = Client::new(cid(1));
+        let deposit_transaction: Transaction = Transaction {
+            tx_type: TxType::Deposit,
+            tx: tid(1),
+            amount: Some(10_f32),
+            client: cid(1),
+            timestamp: None,
+        };
+        assert_eq!((), client.consume_deposit(deposit_transaction).unwrap());
+
+        let dispute_transaction: Transaction = Transaction {
+            tx_type: TxType::Dispute,
+            tx: tid(1),
+            amount: None,
+            client: cid(1),
+            timestamp: None,
+        };
+        assert_eq!((), client.consume_dispute(dispute_transaction).unwrap());
+        assert!(client.audit().is_empty());
+
+        // Tamper with the balances outside of the consumers
+        client.increase_available_amount(1_f32);
+        client.decrease_held_amount(11_f32);
+        client.lock_account(true);
+
+        assert_eq!(
+            vec![
+                "Client 1: available amount is 1.0000, recomputed 0.0000.",
+                "Client 1: held amount is -1.0000, recomputed 10.0000.",
+                "Client 1: account locked is true, recomputed false.",
+                "Client 1: held amount -1.0000 is negative.",
+            ],
+            client.audit()
+        );
+
+        // The lock is recomputed from the chargebacks, not from the lock state
+        let mut client: Client = Client::new(cid(2));
+        let deposit_transaction: Transaction = Transaction {
+            client: cid(2),
+            ..deposit_transaction
+        };
+        assert_eq!((), client.consume_deposit(deposit_transaction).unwrap());
+        assert_eq!(
+            (),
+            client
+                .consume_dispute(Transaction {
+                    client: cid(2),
+                    ..dispute_transaction
+                })
+                .unwrap()
+        );
+        assert_eq!(
+            (),
+            client
+                .consume_chargeback(Transaction {
+                    tx_type: TxType::Chargeback,
+                    client: cid(2),
+                    ..dispute_transaction
+                })
+                .unwrap()
+        );
+        assert!(client.audit().is_empty());
+
+        client.lock_account(false);
+        client.transactions.remove(&tid(1));
+        assert_eq!(
+            vec![
+                "Client 2: account locked is false, recomputed true.",
+                "Client 2: chargedback transaction 1 is missing from the transaction map.",
+            ],
+            client.audit()
+        );
+    }
+
+    #[test]
+    fn test_client_credit_limit() {
+        let mut client: Client = Client::new(cid(1));
+        client.set_credit_limit(5_f32);
+        client.increase_available_amount(2_f32);
+        let mut withdraw_transaction: Transaction = Transaction {
+            tx_type: TxType::Withdrawal,
+            tx: tid(1),
+            amount: Some(6_f32),
+            client: cid(1),
+            timestamp: None,
+        };
+
+        // Withdraw into the credit line
+        assert_eq!((), client.consume_withdrawal(withdraw_transaction).unwrap());
+        assert_eq!(-4_f32, client.get_available_amount());
+        assert_eq!(4_f32, client.get_overdraft_amount());
+        assert_eq!(4_f32, client.get_peak_overdraft());
+
+        // Credit line is exhausted
+        withdraw_transaction.tx = tid(2);
+        withdraw_transaction.amount = Some(1.5_f32);
+        assert_eq!(
+            "PROCESSOR ERROR: Invalid withdrawal transaction 2. Available amount is smaller than withdraw amount.",
+            client
+                .consume_withdrawal(withdraw_transaction)
+                .unwrap_err()
+                .to_string()
+        );
+
+        // Covering the overdraft keeps the peak
+        client.increase_available_amount(10_f32);
+        assert_eq!(0_f32, client.get_overdraft_amount());
+        assert_eq!(4_f32, client.get_peak_overdraft());
+    }
+
+    #[test]
+    fn test_client_open_account() {
+        let mut client: Client = Client::new(cid(1));
+        assert_eq!((), client.open_account(25.5_f32, 2_f32, true).unwrap());
+        assert_eq!(25.5_f32, client.get_available_amount());
+        assert_eq!(2_f32, client.get_held_amount());
+        assert!(client.is_locked());
+        assert_eq!(
+            -275000,
+            client.get_ledger().get_balance(Account::OpeningBalance)
+        );
+
+        assert_eq!(
+            "PROCESSOR ERROR: Client 1 already has a balance and can't be opened again.",
+            client
+                .open_account(1_f32, 0_f32, false)
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "PROCESSOR ERROR: Client 2 cannot be opened with a negative held amount.",
+            Client::new(cid(2))
+                .open_account(1_f32, -1_f32, false)
+                .unwrap_err()
+                .to_string()
+        );
+        assert!(client.audit().is_empty());
+
+        // Reopening replaces the opening balances and their lock
+        assert_eq!((), client.reopen_account(3_f32, 1_f32, false).unwrap());
+        assert_eq!(3_f32, client.get_available_amount());
+        assert_eq!(1_f32, client.get_held_amount());
+        assert!(!client.is_locked());
+        assert_eq!(None, client.get_lock_reason());
+        assert_eq!(
+            -40000,
+            client.get_ledger().get_balance(Account::OpeningBalance)
+        );
+        assert!(client.audit().is_empty());
+        assert_eq!((), client.check_ledger().unwrap());
+
+        let deposit_transaction: Transaction = Transaction {
+            tx_type: TxType::Deposit,
+            tx: tid(1),
+            amount: Some(1_f32),
+            client: cid(1),
+            timestamp: None,
+        };
+        assert_eq!((), client.consume_deposit(deposit_transaction).unwrap());
+        assert_eq!(
+            "PROCESSOR ERROR: Client 1 already has transactions and can't be opened again.",
+            client
+                .reopen_account(1_f32, 0_f32, false)
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_client_reversal() {
+        let mut client: Client = Client::new(cid(1));
+        let deposit_transaction: Transaction = Transaction {
+            tx_type: TxType::Deposit,
+            tx: tid(1),
+            amount: Some(10_f32),
+            client: cid(1),
+            timestamp: None,
+        };
+        let withdraw_transaction: Transaction = Transaction {
+            tx_type: TxType::Withdrawal,
+            tx: tid(2),
+            amount: Some(8_f32),
+            client: cid(1),
+            timestamp: None,
+        };
+        let reversal_transaction: Transaction = Transaction {
+            tx_type: TxType::Reversal,
+            tx: tid(1),
+            amount: None,
+            client: cid(1),
+            timestamp: None,
+        };
+        assert_eq!((), client.consume_deposit(deposit_transaction).unwrap());
+        assert_eq!((), client.consume_withdrawal(withdraw_transaction).unwrap());
+
+        // Reversing the deposit overdraws the account without locking it
+        assert_eq!((), client.consume_reversal(reversal_transaction).unwrap());
+        assert_eq!(-8_f32, client.get_available_amount());
+        assert!(!client.is_locked());
+        assert_eq!(Some(&reversal_transaction), client.get_reversal(tid(1)));
+        assert_eq!(
+            "PROCESSOR ERROR: Transaction 1 is already reversed.",
+            client
+                .consume_reversal(reversal_transaction)
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "PROCESSOR ERROR: Transaction 1 was reversed and can't be disputed.",
+            client
+                .consume_dispute(Transaction {
+                    tx_type: TxType::Dispute,
+                    ..reversal_transaction
+                })
+                .unwrap_err()
+                .to_string()
+        );
+
+        // Disputed transactions can't be reversed
+        assert_eq!(
+            (),
+            client
+                .consume_dispute(Transaction {
+                    tx_type: TxType::Dispute,
+                    tx: tid(2),
+                    ..reversal_transaction
+                })
+                .unwrap()
+        );
+        assert_eq!(
+            "PROCESSOR ERROR: Transaction 2 is disputed/chargedback and can't be reversed.",
+            client
+                .consume_reversal(Transaction {
+                    tx: tid(2),
+                    ..reversal_transaction
+                })
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "PROCESSOR ERROR: Transaction 3 isn't registered for client 1.",
+            client
+                .consume_reversal(Transaction {
+                    tx: tid(3),
+                    ..reversal_transaction
+                })
+                .unwrap_err()
+                .to_string()
+        );
+
+        assert_eq!(
+            80000,
+            client.get_ledger().get_balance(Account::BankClearing)
+        );
+        assert!(client.audit().is_empty());
+    }
+
+    #[test]
+    fn test_client_authorizations() {
+        let mut client: Client = Client::new(cid(1));
+        client.set_credit_limit(2_f32);
+        assert_eq!(
+            (),
+            client
+                .consume_deposit(Transaction {
+                    tx_type: TxType::Deposit,
+                    tx: tid(1),
+                    amount: Some(10_f32),
+                    client: cid(1),
+                    timestamp: None,
+                })
+                .unwrap()
+        );
+        let authorize_transaction: Transaction = Transaction {
+            tx_type: TxType::Authorize,
+            tx: tid(2),
+            amount: Some(6_f32),
+            client: cid(1),
+            timestamp: None,
+        };
+        let capture_transaction: Transaction = Transaction {
+            tx_type: TxType::Capture,
+            tx: tid(2),
+            amount: Some(4_f32),
+            client: cid(1),
+            timestamp: None,
+        };
+        let void_transaction: Transaction = Transaction {
+            tx_type: TxType::Void,
+            tx: tid(3),
+            amount: None,
+            client: cid(1),
+            timestamp: None,
+        };
+
+        // Authorized funds move from available to held
+        assert_eq!((), client.consume_authorize(authorize_transaction).unwrap());
+        assert_eq!(4_f32, client.get_available_amount());
+        assert_eq!(6_f32, client.get_held_amount());
+        assert!(client.check_open_authorization(tid(2)));
+        assert_eq!(
+            "PROCESSOR ERROR: Invalid capture transaction 2. Authorized amount is smaller than captured amount.",
+            client
+                .consume_capture(Transaction {
+                    amount: Some(7_f32),
+                    ..capture_transaction
+                })
+                .unwrap_err()
+                .to_string()
+        );
+
+        assert_eq!(
+            "PROCESSOR ERROR: Transaction with ID: 2 cannot have negative or 0 amount.",
+            client
+                .consume_capture(Transaction {
+                    amount: Some(0_f32),
+                    ..capture_transaction
+                })
+                .unwrap_err()
+                .to_string()
+        );
+
+        // A partial capture releases the rest of the hold
+        assert_eq!((), client.consume_capture(capture_transaction).unwrap());
+        assert_eq!(6_f32, client.get_available_amount());
+        assert_eq!(0_f32, client.get_held_amount());
+        assert_eq!(4_f32, client.get_capture(tid(2)).unwrap().get_amount());
+        assert_eq!(
+            "PROCESSOR ERROR: Authorization 2 is already captured/voided.",
+            client
+                .consume_void(Transaction {
+                    tx: tid(2),
+                    ..void_transaction
+                })
+                .unwrap_err()
+                .to_string()
+        );
+
+        // Authorizations can use the credit line, voids give the funds back
+        assert_eq!(
+            (),
+            client
+                .consume_authorize(Transaction {
+                    tx: tid(3),
+                    amount: Some(8_f32),
+                    ..authorize_transaction
+                })
+                .unwrap()
+        );
+        assert_eq!(-2_f32, client.get_available_amount());
+        assert_eq!((), client.consume_void(void_transaction).unwrap());
+        assert_eq!(6_f32, client.get_available_amount());
+        assert_eq!(0_f32, client.get_held_amount());
+
+        assert_eq!(
+            "PROCESSOR ERROR: Only AUTHORIZE transactions can be captured.",
+            client
+                .consume_capture(Transaction {
+                    tx: tid(1),
+                    ..capture_transaction
+                })
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            -60000,
+            client.get_ledger().get_balance(Account::BankClearing)
+        );
+        assert!(client.audit().is_empty());
+
+        client.lock_account(true);
+        assert_eq!(
+            "PROCESSOR ERROR: Locked accounts cannot accept authorizations.",
+            client
+                .consume_authorize(Transaction {
+                    tx: tid(4),
+                    ..authorize_transaction
+                })
+                .unwrap_err()
+                .to_string()
+        );
+    }
 }
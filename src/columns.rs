@@ -0,0 +1,386 @@
+use crate::error::p_error;
+use crate::transaction::{parse_amount, Transaction, UnexpectedAmount};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+lazy_static! {
+    // Order of the columns given to the Transaction deserializer
+    static ref TRANSACTION_HEADER: csv::ByteRecord =
+        csv::ByteRecord::from(vec!["type", "client", "tx", "amount", "timestamp"]);
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Type,
+    Client,
+    Tx,
+    Amount,
+    Timestamp,
+    // Groups rows that are applied all-or-nothing
+    Batch,
+}
+
+// Columns of the Transaction, in the order of TRANSACTION_HEADER
+const COLUMNS: [Column; 5] = [
+    Column::Type,
+    Column::Client,
+    Column::Tx,
+    Column::Amount,
+    Column::Timestamp,
+];
+
+impl Column {
+    // Rows without type, client or tx can't be processed
+    pub fn is_mandatory(self) -> bool {
+        matches!(self, Column::Type | Column::Client | Column::Tx)
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Column::Type => write!(f, "type"),
+            Column::Client => write!(f, "client"),
+            Column::Tx => write!(f, "tx"),
+            Column::Amount => write!(f, "amount"),
+            Column::Timestamp => write!(f, "timestamp"),
+            Column::Batch => write!(f, "batch"),
+        }
+    }
+}
+
+// One row of the column aliases file, e.g.
+//
+// alias,column
+// transaction_kind,type
+// customer,client
+#[derive(Deserialize, Debug)]
+struct AliasRecord {
+    alias: String,
+    column: Column,
+}
+
+/*******************************
+< ColumnAliases >
+
+Header names accepted for every column. Names are compared without case,
+and every column is known by its own name.
+
+*******************************/
+#[derive(Debug, Clone)]
+pub struct ColumnAliases {
+    aliases: HashMap<String, Column>,
+}
+
+impl Default for ColumnAliases {
+    fn default() -> Self {
+        let mut aliases: HashMap<String, Column> = HashMap::new();
+        for column in COLUMNS.into_iter().chain([Column::Batch]) {
+            aliases.insert(column.to_string(), column);
+        }
+        for (alias, column) in [
+            ("tx_type", Column::Type),
+            ("transaction_type", Column::Type),
+            ("client_id", Column::Client),
+            ("tx_id", Column::Tx),
+            ("transaction_id", Column::Tx),
+            ("time", Column::Timestamp),
+            ("batch_id", Column::Batch),
+        ] {
+            aliases.insert(alias.to_string(), column);
+        }
+
+        Self { aliases }
+    }
+}
+
+impl ColumnAliases {
+    pub fn add(&mut self, alias: &str, column: Column) {
+        self.aliases.insert(alias.trim().to_lowercase(), column);
+    }
+
+    pub fn get_column(&self, name: &str) -> Option<Column> {
+        self.aliases.get(&name.trim().to_lowercase()).copied()
+    }
+
+    // Adds the aliases of a CSV file to the default ones
+    pub fn load(&mut self, filename: &str) -> Result<(), Box<dyn Error>> {
+        let mut csv_reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_path(filename)?;
+
+        for row in csv_reader.deserialize() {
+            let record: AliasRecord = row?;
+            self.add(&record.alias, record.column);
+        }
+
+        Ok(())
+    }
+}
+
+/*******************************
+< ColumnMap >
+
+Position of every column in the rows of an input file, read from its
+header. Columns the processor doesn't know are ignored.
+
+*******************************/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnMap {
+    // Position of type, client, tx, amount, timestamp and batch
+    positions: [Option<usize>; 6],
+    // Amounts given to dispute, resolve and chargeback rows
+    unexpected_amount: UnexpectedAmount,
+}
+
+impl ColumnMap {
+    // Columns of files without a header: type, client, tx and the optional
+    // amount and timestamp
+    pub fn positional() -> Self {
+        Self {
+            positions: [Some(0), Some(1), Some(2), Some(3), Some(4), None],
+            unexpected_amount: UnexpectedAmount::default(),
+        }
+    }
+
+    pub fn from_header(
+        header: &csv::ByteRecord,
+        aliases: &ColumnAliases,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut positions: [Option<usize>; 6] = [None; 6];
+
+        for (position, name) in header.iter().enumerate() {
+            let name: String = String::from_utf8_lossy(name).to_string();
+            let column: Column = match aliases.get_column(&name) {
+                Some(column) => column,
+                None => continue,
+            };
+
+            let index: usize = column as usize;
+            if let Some(previous) = positions[index] {
+                return p_error(format!(
+                    "Columns {} and {} of the header are both the {} column.",
+                    previous + 1,
+                    position + 1,
+                    column
+                ));
+            }
+            positions[index] = Some(position);
+        }
+
+        let missing: Vec<String> = COLUMNS
+            .iter()
+            .filter(|column| column.is_mandatory() && positions[**column as usize].is_none())
+            .map(|column| column.to_string())
+            .collect();
+        if !missing.is_empty() {
+            return p_error(format!(
+                "Header has no {} column. Use --headerless for files without a header.",
+                missing.join(", ")
+            ));
+        }
+
+        Ok(Self {
+            positions,
+            unexpected_amount: UnexpectedAmount::default(),
+        })
+    }
+
+    pub fn set_unexpected_amount(&mut self, unexpected_amount: UnexpectedAmount) {
+        self.unexpected_amount = unexpected_amount;
+    }
+
+    // Batch id of a row, rows without one aren't part of a batch
+    pub fn get_batch(&self, record: &csv::ByteRecord) -> Option<String> {
+        let field: &[u8] = record
+            .get(self.positions[Column::Batch as usize]?)?
+            .trim_ascii();
+        if field.is_empty() {
+            return None;
+        }
+
+        Some(String::from_utf8_lossy(field).to_string())
+    }
+
+    pub fn parse(&self, record: &csv::ByteRecord) -> Result<Transaction, Box<dyn Error>> {
+        let mut fields: csv::ByteRecord = csv::ByteRecord::new();
+        let mut amount: String = String::new();
+
+        for column in COLUMNS {
+            let field: Option<&[u8]> =
+                self.positions[column as usize].and_then(|position| record.get(position));
+            match field {
+                // The amount is checked once the transaction type is known
+                Some(field) if column == Column::Amount => {
+                    amount = String::from_utf8_lossy(field.trim_ascii()).to_string();
+                    fields.push_field(b"");
+                }
+                // The first row of a headerless file isn't trimmed by the reader
+                Some(field) => fields.push_field(field.trim_ascii()),
+                None if column.is_mandatory() => {
+                    return Err(format!(
+                        "Row has {} field(s) and no {} column.",
+                        record.len(),
+                        column
+                    )
+                    .into())
+                }
+                None => fields.push_field(b""),
+            }
+        }
+
+        let mut transaction: Transaction = fields
+            .deserialize(Some(&TRANSACTION_HEADER))
+            .map_err(|error| format!("Deserialization error: {}.", error))?;
+
+        match (transaction.tx_type.has_amount(), amount.is_empty()) {
+            (true, true) if transaction.tx_type.has_optional_amount() => {}
+            (true, true) => {
+                return Err(format!(
+                    "Missing amount for {} transaction {}.",
+                    transaction.tx_type, transaction.tx
+                )
+                .into())
+            }
+            (true, false) => transaction.amount = Some(parse_amount(&amount)?),
+            (false, false) if self.unexpected_amount == UnexpectedAmount::Reject => {
+                return Err(format!(
+                    "Unexpected amount {} for {} transaction {}.",
+                    amount, transaction.tx_type, transaction.tx
+                )
+                .into())
+            }
+            (false, false) => eprintln!(
+                "WARNING: Amount {} of {} transaction {} is ignored.",
+                amount, transaction.tx_type, transaction.tx
+            ),
+            (false, true) => {}
+        }
+
+        Ok(transaction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::{cid, tid};
+    use crate::transaction::TxType;
+
+    #[test]
+    fn test_column_map_from_header() {
+        let mut aliases: ColumnAliases = ColumnAliases::default();
+        aliases.add("Customer", Column::Client);
+
+        let header = csv::ByteRecord::from(vec!["tx_id", "note", "CUSTOMER", "amount", "type"]);
+        let map: ColumnMap = ColumnMap::from_header(&header, &aliases).unwrap();
+
+        assert_eq!(
+            Transaction {
+                tx_type: TxType::Deposit,
+                client: cid(2),
+                tx: tid(7),
+                amount: Some(1.5_f32),
+                timestamp: None,
+            },
+            map.parse(&csv::ByteRecord::from(vec![
+                "7", "x", "2", "1.5", "deposit"
+            ]))
+            .unwrap()
+        );
+        assert_eq!(
+            "Row has 3 field(s) and no type column.",
+            map.parse(&csv::ByteRecord::from(vec!["7", "x", "2"]))
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_column_map_errors() {
+        let aliases: ColumnAliases = ColumnAliases::default();
+
+        assert_eq!(
+            "PROCESSOR ERROR: Header has no client, tx column. Use --headerless for files without a header.",
+            ColumnMap::from_header(&csv::ByteRecord::from(vec!["type", "amount"]), &aliases)
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "PROCESSOR ERROR: Columns 2 and 4 of the header are both the client column.",
+            ColumnMap::from_header(
+                &csv::ByteRecord::from(vec!["type", "client", "tx", "client_id"]),
+                &aliases
+            )
+            .unwrap_err()
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_positional_column_map() {
+        let map: ColumnMap = ColumnMap::positional();
+
+        assert_eq!(
+            Some(30),
+            map.parse(&csv::ByteRecord::from(vec!["dispute", "1", "2", "", "30"]))
+                .unwrap()
+                .get_timestamp()
+        );
+        assert_eq!(
+            0_f32,
+            map.parse(&csv::ByteRecord::from(vec!["dispute", "1", "2"]))
+                .unwrap()
+                .get_amount()
+        );
+    }
+
+    #[test]
+    fn test_amount_checks() {
+        let mut map: ColumnMap = ColumnMap::positional();
+        let parse = |map: &ColumnMap, row: Vec<&str>| {
+            map.parse(&csv::ByteRecord::from(row))
+                .map_err(|error| error.to_string())
+        };
+
+        assert_eq!(
+            Err("Missing amount for deposit transaction 1.".to_string()),
+            parse(&map, vec!["deposit", "1", "1"])
+        );
+        assert_eq!(
+            Err("Missing amount for withdrawal transaction 2.".to_string()),
+            parse(&map, vec!["withdrawal", "1", "2", " ", "10"])
+        );
+        assert_eq!(
+            Err("Amount 1.23456 has more than 4 decimal places.".to_string()),
+            parse(&map, vec!["deposit", "1", "1", "1.23456"])
+        );
+        assert_eq!(
+            Err("Unexpected amount 5.0 for chargeback transaction 1.".to_string()),
+            parse(&map, vec!["chargeback", "1", "1", "5.0"])
+        );
+        assert_eq!(
+            Err("Missing amount for authorize transaction 3.".to_string()),
+            parse(&map, vec!["authorize", "1", "3"])
+        );
+        // Captures without an amount debit the whole authorization
+        assert_eq!(
+            Ok(None),
+            parse(&map, vec!["capture", "1", "3"]).map(|tx| tx.amount)
+        );
+        assert_eq!(
+            Ok(Some(0_f32)),
+            parse(&map, vec!["capture", "1", "3", "0"]).map(|tx| tx.amount)
+        );
+
+        // Ignored amounts aren't validated
+        map.set_unexpected_amount(UnexpectedAmount::Ignore);
+        assert_eq!(
+            Ok(0_f32),
+            parse(&map, vec!["resolve", "1", "1", "NaN"]).map(|tx| tx.get_amount())
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use flate2::read::MultiGzDecoder;
+use ruzstd::decoding::StreamingDecoder;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    // Compression of a stream, from its first bytes. The extension of the
+    // file isn't used.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else if bytes.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+pub fn detect_compression(filename: &str) -> Result<Compression, Box<dyn Error>> {
+    let mut reader: BufReader<File> = BufReader::new(File::open(filename)?);
+
+    Ok(Compression::detect(reader.fill_buf()?))
+}
+
+// Opens a file, decompressing it on the fly when it's compressed
+pub fn open_input(filename: &str) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    let mut reader: BufReader<File> = BufReader::new(File::open(filename)?);
+
+    // The magic bytes stay in the buffer for the decoder
+    Ok(match Compression::detect(reader.fill_buf()?) {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Compression::Zstd => Box::new(StreamingDecoder::new(reader)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_compressed_input() {
+        let read = |file: &str| -> String {
+            let mut content: String = String::new();
+            open_input(&format!(
+                "{}/tests/resources/{}",
+                env!("CARGO_MANIFEST_DIR"),
+                file
+            ))
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+            content
+        };
+
+        let plain: String = read("disputes_example.csv");
+        assert_eq!(plain, read("disputes_example.csv.gz"));
+        assert_eq!(plain, read("disputes_example.csv.zst"));
+    }
+
+    #[test]
+    fn test_detect_compression() {
+        assert_eq!(Compression::Gzip, Compression::detect(&[0x1f, 0x8b, 0x08]));
+        assert_eq!(
+            Compression::Zstd,
+            Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd, 0x00])
+        );
+        assert_eq!(Compression::None, Compression::detect(b"type,client"));
+        assert_eq!(Compression::None, Compression::detect(&[0x1f]));
+    }
+}
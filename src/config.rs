@@ -0,0 +1,178 @@
+use crate::client::ClientStatus;
+use crate::error::p_error;
+use crate::ids::ClientId;
+use crate::ledger::to_minor_units;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::error::Error;
+
+// One row of the client registry. Only the client column is mandatory, e.g.
+//
+// client,name,status,credit_limit,initial_balance
+// 1,Jane Doe,active,100.0,25.5
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ClientConfig {
+    pub client: ClientId,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub status: Option<ClientStatus>,
+    #[serde(default)]
+    pub credit_limit: Option<f32>,
+    #[serde(default)]
+    pub initial_balance: Option<f32>,
+}
+
+impl ClientConfig {
+    pub fn get_credit_limit(&self) -> f32 {
+        self.credit_limit.unwrap_or(0_f32)
+    }
+
+    pub fn get_initial_balance(&self) -> f32 {
+        self.initial_balance.unwrap_or(0_f32)
+    }
+
+    pub fn get_status(&self) -> ClientStatus {
+        self.status.unwrap_or_default()
+    }
+}
+
+pub fn load_client_configs(filename: &str) -> Result<Vec<ClientConfig>, Box<dyn Error>> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(filename)?;
+
+    let mut configs: Vec<ClientConfig> = Vec::new();
+    let mut client_ids: HashSet<ClientId> = HashSet::new();
+    for row in csv_reader.deserialize() {
+        let config: ClientConfig = row?;
+
+        if !client_ids.insert(config.client) {
+            return p_error(format!(
+                "Client {} is registered more than once.",
+                config.client
+            ));
+        }
+        if !config.get_credit_limit().is_finite() {
+            return p_error(format!(
+                "Client {} has an invalid credit limit.",
+                config.client
+            ));
+        }
+        if config.get_credit_limit() < 0_f32 {
+            return p_error(format!(
+                "Client {} cannot have a negative credit limit.",
+                config.client
+            ));
+        }
+        if !config.get_initial_balance().is_finite() {
+            return p_error(format!(
+                "Client {} has an invalid initial balance.",
+                config.client
+            ));
+        }
+
+        configs.push(config);
+    }
+
+    Ok(configs)
+}
+
+// One row of the opening balances file. The layout is the same as the
+// processor output, so a previous run can be used as opening balances
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct OpeningBalance {
+    pub client: ClientId,
+    pub available: f32,
+    pub held: f32,
+    pub total: f32,
+    pub locked: bool,
+}
+
+pub fn load_opening_balances(filename: &str) -> Result<Vec<OpeningBalance>, Box<dyn Error>> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(filename)?;
+
+    let mut opening_balances: Vec<OpeningBalance> = Vec::new();
+    let mut client_ids: HashSet<ClientId> = HashSet::new();
+    for row in csv_reader.deserialize() {
+        let opening_balance: OpeningBalance = row?;
+
+        if !client_ids.insert(opening_balance.client) {
+            return p_error(format!(
+                "Client {} has more than one opening balance.",
+                opening_balance.client
+            ));
+        }
+        if !opening_balance.available.is_finite() || !opening_balance.held.is_finite() {
+            return p_error(format!(
+                "Client {} has an invalid opening balance.",
+                opening_balance.client
+            ));
+        }
+        if (to_minor_units(opening_balance.total)
+            - to_minor_units(opening_balance.available)
+            - to_minor_units(opening_balance.held))
+        .abs()
+            > 1
+        {
+            return p_error(format!(
+                "Client {} opening total {:.4} differs from available + held.",
+                opening_balance.client, opening_balance.total
+            ));
+        }
+
+        opening_balances.push(opening_balance);
+    }
+
+    Ok(opening_balances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_client_configs() {
+        let configs: Vec<ClientConfig> = load_client_configs(&format!(
+            "{}/tests/resources/client_registry.csv",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+
+        assert_eq!(3, configs.len());
+        assert_eq!(Some("Jane Doe".to_string()), configs[0].name);
+        assert_eq!(ClientStatus::Active, configs[0].get_status());
+        assert_eq!(100_f32, configs[0].get_credit_limit());
+        assert_eq!(25.5_f32, configs[0].get_initial_balance());
+        assert_eq!(None, configs[2].name);
+        assert_eq!(ClientStatus::Closed, configs[2].get_status());
+        assert_eq!(0_f32, configs[2].get_credit_limit());
+        assert_eq!(0_f32, configs[2].get_initial_balance());
+    }
+
+    #[test]
+    fn test_invalid_credit_limits() {
+        let filename: String = std::env::temp_dir()
+            .join("toy_processor_credit_limits.csv")
+            .display()
+            .to_string();
+
+        for (credit_limit, error) in [
+            ("-1", "Client 1 cannot have a negative credit limit."),
+            ("NaN", "Client 1 has an invalid credit limit."),
+            ("inf", "Client 1 has an invalid credit limit."),
+        ] {
+            std::fs::write(
+                &filename,
+                format!("client,credit_limit\n1,{}\n", credit_limit),
+            )
+            .unwrap();
+            assert_eq!(
+                format!("PROCESSOR ERROR: {}", error),
+                load_client_configs(&filename).unwrap_err().to_string()
+            );
+        }
+    }
+}
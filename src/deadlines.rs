@@ -0,0 +1,218 @@
+use crate::ids::{ClientId, TxId};
+use std::collections::BTreeSet;
+use std::fmt;
+
+// Clock the deadlines are measured with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeadlineUnit {
+    // Processed rows
+    Rows,
+    // Seconds of the timestamp column
+    Seconds,
+}
+
+impl fmt::Display for DeadlineUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeadlineUnit::Rows => write!(f, "row"),
+            DeadlineUnit::Seconds => write!(f, "timestamp"),
+        }
+    }
+}
+
+// Change to the deadlines of an open group of rows, undone on rollback
+#[derive(Debug, Clone, Copy)]
+enum DeadlineChange {
+    Added((u64, ClientId, TxId)),
+    Expired((u64, ClientId, TxId)),
+}
+
+// A deadline that hasn't passed yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PendingDeadline {
+    pub client: ClientId,
+    pub tx: TxId,
+    pub deadline: u64,
+    pub unit: DeadlineUnit,
+}
+
+/*******************************
+< Deadlines >
+
+Keeps the deadline of every opened dispute or authorization, measured
+in processed rows or in seconds of event time. Deadlines are kept
+ordered by expiry. What happens once a deadline passes is up to the
+processor. While a group of rows is open, every change is logged so the
+group can be rolled back.
+
+*******************************/
+#[derive(Debug, Clone)]
+pub struct Deadlines {
+    length: u64,
+    unit: DeadlineUnit,
+    // (deadline, client, tx)
+    deadlines: BTreeSet<(u64, ClientId, TxId)>,
+    // Changes of the open group, if any
+    undo: Option<Vec<DeadlineChange>>,
+}
+
+impl Deadlines {
+    pub fn new(length: u64, unit: DeadlineUnit) -> Self {
+        Self {
+            length,
+            unit,
+            deadlines: BTreeSet::new(),
+            undo: None,
+        }
+    }
+
+    // Starts the deadline of a transaction. Time based deadlines need its
+    // timestamp, transactions without one never expire.
+    pub fn add(&mut self, row: u64, timestamp: Option<u64>, client_id: ClientId, tx_id: TxId) {
+        let start: Option<u64> = match self.unit {
+            DeadlineUnit::Rows => Some(row),
+            DeadlineUnit::Seconds => timestamp,
+        };
+
+        // A deadline past the end of the clock never passes
+        if let Some(deadline) = start.and_then(|start| start.checked_add(self.length)) {
+            let entry: (u64, ClientId, TxId) = (deadline, client_id, tx_id);
+            if self.deadlines.insert(entry) {
+                self.log(DeadlineChange::Added(entry));
+            }
+        }
+    }
+
+    // Transactions whose deadline is reached at the given row or event time
+    pub fn expire(&mut self, row: u64, timestamp: Option<u64>) -> Vec<(ClientId, TxId)> {
+        let now: u64 = match self.unit {
+            DeadlineUnit::Rows => row,
+            DeadlineUnit::Seconds => match timestamp {
+                Some(timestamp) => timestamp,
+                None => return Vec::new(),
+            },
+        };
+        let mut expired: Vec<(ClientId, TxId)> = Vec::new();
+
+        while let Some(entry) = self.deadlines.first().copied() {
+            let (deadline, client_id, tx_id) = entry;
+            if deadline > now {
+                break;
+            }
+            expired.push((client_id, tx_id));
+            self.deadlines.pop_first();
+            self.log(DeadlineChange::Expired(entry));
+        }
+
+        expired
+    }
+
+    // Starts logging changes for a group of rows
+    pub fn begin_group(&mut self) {
+        self.undo = Some(Vec::new());
+    }
+
+    // Keeps the changes of the group
+    pub fn commit_group(&mut self) {
+        self.undo = None;
+    }
+
+    // Undoes every change of the group, latest first
+    pub fn rollback_group(&mut self) {
+        for change in self.undo.take().unwrap_or_default().into_iter().rev() {
+            match change {
+                DeadlineChange::Added(entry) => self.deadlines.remove(&entry),
+                DeadlineChange::Expired(entry) => self.deadlines.insert(entry),
+            };
+        }
+    }
+
+    fn log(&mut self, change: DeadlineChange) {
+        if let Some(undo) = self.undo.as_mut() {
+            undo.push(change);
+        }
+    }
+
+    // Deadlines that haven't passed yet, in expiry order
+    pub fn pending(&self) -> impl Iterator<Item = PendingDeadline> + '_ {
+        self.deadlines
+            .iter()
+            .map(|&(deadline, client, tx)| PendingDeadline {
+                client,
+                tx,
+                deadline,
+                unit: self.unit,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::{cid, tid};
+
+    #[test]
+    fn test_deadlines() {
+        let mut deadlines: Deadlines = Deadlines::new(3, DeadlineUnit::Rows);
+        deadlines.add(1, None, cid(1), tid(10));
+        deadlines.add(2, None, cid(2), tid(20));
+
+        assert!(deadlines.expire(3, None).is_empty());
+        assert_eq!(vec![(cid(1), tid(10))], deadlines.expire(4, None));
+        assert_eq!(vec![(cid(2), tid(20))], deadlines.expire(8, None));
+        assert!(deadlines.expire(9, None).is_empty());
+
+        deadlines.add(9, None, cid(3), tid(30));
+        deadlines.add(10, None, cid(1), tid(40));
+        assert_eq!(
+            vec![(12, cid(3), tid(30)), (13, cid(1), tid(40))],
+            deadlines
+                .pending()
+                .map(|pending| (pending.deadline, pending.client, pending.tx))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(vec![(cid(3), tid(30))], deadlines.expire(12, None));
+    }
+
+    #[test]
+    fn test_group_rollback() {
+        let mut deadlines: Deadlines = Deadlines::new(2, DeadlineUnit::Rows);
+        deadlines.add(1, None, cid(1), tid(10));
+        deadlines.add(2, None, cid(2), tid(20));
+
+        deadlines.begin_group();
+        assert_eq!(vec![(cid(1), tid(10))], deadlines.expire(3, None));
+        deadlines.add(3, None, cid(3), tid(30));
+        deadlines.rollback_group();
+        assert_eq!(
+            vec![(cid(1), tid(10)), (cid(2), tid(20))],
+            deadlines.expire(4, None)
+        );
+
+        deadlines.begin_group();
+        deadlines.add(5, None, cid(3), tid(30));
+        deadlines.commit_group();
+        deadlines.rollback_group();
+        assert_eq!(vec![(cid(3), tid(30))], deadlines.expire(7, None));
+    }
+
+    #[test]
+    fn test_deadlines_in_seconds() {
+        let mut deadlines: Deadlines = Deadlines::new(60, DeadlineUnit::Seconds);
+        deadlines.add(1, Some(1000), cid(1), tid(10));
+        deadlines.add(2, Some(900), cid(2), tid(20));
+        // Transactions without a timestamp don't get a deadline
+        deadlines.add(3, None, cid(3), tid(30));
+
+        assert!(deadlines.expire(100, None).is_empty());
+        assert_eq!(vec![(cid(2), tid(20))], deadlines.expire(4, Some(1000)));
+        assert_eq!(vec![(cid(1), tid(10))], deadlines.expire(5, Some(1060)));
+        assert!(deadlines.expire(6, Some(99999)).is_empty());
+
+        // Deadlines past the end of the clock never pass
+        deadlines.add(7, Some(u64::MAX - 60), cid(4), tid(40));
+        deadlines.add(8, Some(u64::MAX), cid(5), tid(50));
+        assert_eq!(vec![(cid(4), tid(40))], deadlines.expire(9, Some(u64::MAX)));
+        assert_eq!(0, deadlines.pending().count());
+    }
+}
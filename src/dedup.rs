@@ -0,0 +1,289 @@
+use crate::error::p_error;
+use crate::ids::{ClientId, TxId};
+use crate::ledger::to_minor_units;
+use crate::transaction::{Transaction, TxType};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+
+// 64 bit FNV-1a, stable between runs so fingerprints can be saved
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+// Fingerprint of the parsed row, so whitespace and amount formatting
+// differences don't hide a duplicate
+pub fn row_fingerprint(transaction: Transaction) -> u64 {
+    let row: String = format!(
+        "{},{},{},{},{}",
+        transaction.get_tx_type(),
+        transaction.get_client_id(),
+        transaction.get_tx_id(),
+        to_minor_units(transaction.get_amount()),
+        transaction
+            .get_timestamp()
+            .map(|timestamp| timestamp.to_string())
+            .unwrap_or_default()
+    );
+
+    fnv1a(FNV_OFFSET, row.as_bytes())
+}
+
+// Rows are unique per type, client and tx once duplicates are skipped
+pub fn row_key(transaction: Transaction) -> (TxType, ClientId, TxId) {
+    (
+        transaction.get_tx_type(),
+        transaction.get_client_id(),
+        transaction.get_tx_id(),
+    )
+}
+
+// Fingerprint of the raw file content
+pub fn file_fingerprint(filename: &str) -> Result<u64, Box<dyn Error>> {
+    let mut file: File = File::open(filename)?;
+    let mut buffer: [u8; 8192] = [0; 8192];
+    let mut hash: u64 = FNV_OFFSET;
+
+    loop {
+        let read: usize = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hash = fnv1a(hash, &buffer[..read]);
+    }
+
+    Ok(hash)
+}
+
+// One row of the deduplication state file, e.g.
+//
+// kind,tx_type,client,tx,fingerprint
+// file,,,,9f2c4e0b1a7d3c55
+// row,deposit,1,1,1b3a0c9e8d7f6a21
+#[derive(Deserialize, Debug)]
+struct StateRecord {
+    kind: String,
+    #[serde(default)]
+    tx_type: Option<TxType>,
+    #[serde(default)]
+    client: Option<ClientId>,
+    #[serde(default)]
+    tx: Option<TxId>,
+    fingerprint: String,
+}
+
+/*******************************
+< Deduplicator >
+
+Remembers the fingerprint of every ingested row and file. Each transaction
+type can appear once per client and tx id, so a row with a known type and tx id is
+either an exact duplicate, skipped silently, or a conflicting one. Rows are
+only remembered once they are accepted, so rejected rows can be sent again.
+
+*******************************/
+#[derive(Debug, Clone, Default)]
+pub struct Deduplicator {
+    // (type, client, tx) of every ingested row
+    rows: HashMap<(TxType, ClientId, TxId), u64>,
+    // Rows waiting to be reordered, not accepted yet
+    pending: HashMap<(TxType, ClientId, TxId), u64>,
+    files: HashSet<u64>,
+}
+
+impl Deduplicator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns true when the row was already ingested, or is waiting to be
+    // reordered
+    pub fn check_row(&self, transaction: Transaction) -> Result<bool, Box<dyn Error>> {
+        let fingerprint: u64 = row_fingerprint(transaction);
+        let key: (TxType, ClientId, TxId) = row_key(transaction);
+
+        match self.rows.get(&key).or_else(|| self.pending.get(&key)) {
+            Some(known) if *known == fingerprint => Ok(true),
+            Some(_) => p_error(format!(
+                "Transaction {} conflicts with an earlier {} row with different content.",
+                transaction.get_tx_id(),
+                transaction.get_tx_type()
+            )),
+            None => Ok(false),
+        }
+    }
+
+    // Remembers a row waiting to be reordered, so its redeliveries are
+    // skipped until it's accepted or rejected
+    pub fn hold_row(&mut self, transaction: Transaction) {
+        self.pending
+            .insert(row_key(transaction), row_fingerprint(transaction));
+    }
+
+    // Remembers an accepted row
+    pub fn record_row(&mut self, transaction: Transaction) {
+        let key: (TxType, ClientId, TxId) = row_key(transaction);
+        self.pending.remove(&key);
+        self.rows.insert(key, row_fingerprint(transaction));
+    }
+
+    // Returns true when a file with the same content was already ingested
+    pub fn check_file(&mut self, fingerprint: u64) -> bool {
+        !self.files.insert(fingerprint)
+    }
+
+    // Forgets a row that was rejected or whose effect was rolled back, so
+    // it can be sent again
+    pub fn forget_row(&mut self, transaction: Transaction) {
+        let key: (TxType, ClientId, TxId) = row_key(transaction);
+        self.pending.remove(&key);
+        self.rows.remove(&key);
+    }
+
+    pub fn forget_file(&mut self, fingerprint: u64) {
+        self.files.remove(&fingerprint);
+    }
+
+    pub fn load(&mut self, filename: &str) -> Result<(), Box<dyn Error>> {
+        let mut csv_reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_path(filename)?;
+
+        for row in csv_reader.deserialize() {
+            let record: StateRecord = row?;
+            let fingerprint: u64 = match u64::from_str_radix(&record.fingerprint, 16) {
+                Ok(fingerprint) => fingerprint,
+                Err(_) => return p_error(format!("Invalid fingerprint {}.", record.fingerprint)),
+            };
+
+            match (
+                record.kind.as_str(),
+                record.tx_type,
+                record.client,
+                record.tx,
+            ) {
+                ("file", _, _, _) => {
+                    self.files.insert(fingerprint);
+                }
+                ("row", Some(tx_type), Some(client), Some(tx)) => {
+                    self.rows.insert((tx_type, client, tx), fingerprint);
+                }
+                _ => {
+                    return p_error(format!(
+                        "Invalid deduplication state row of kind {}.",
+                        record.kind
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn save(&self, filename: &str) -> Result<(), Box<dyn Error>> {
+        let mut csv_writer = csv::Writer::from_path(filename)?;
+        csv_writer.write_record(["kind", "tx_type", "client", "tx", "fingerprint"])?;
+
+        let mut files: Vec<&u64> = self.files.iter().collect();
+        files.sort();
+        for fingerprint in files {
+            csv_writer.write_record(["file", "", "", "", &format!("{:016x}", fingerprint)])?;
+        }
+
+        let mut rows: Vec<(&(TxType, ClientId, TxId), &u64)> = self.rows.iter().collect();
+        rows.sort_by_key(|((tx_type, client, tx), _)| (*client, *tx, tx_type.to_string()));
+        for ((tx_type, client, tx), fingerprint) in rows {
+            csv_writer.write_record([
+                "row",
+                &tx_type.to_string(),
+                &client.to_string(),
+                &tx.to_string(),
+                &format!("{:016x}", fingerprint),
+            ])?;
+        }
+
+        csv_writer.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::{cid, tid};
+
+    #[test]
+    fn test_check_row() {
+        let mut deduplicator: Deduplicator = Deduplicator::new();
+        let deposit: Transaction = Transaction {
+            tx_type: TxType::Deposit,
+            client: cid(1),
+            tx: tid(1),
+            amount: Some(2.5_f32),
+            timestamp: None,
+        };
+        let dispute: Transaction = Transaction {
+            tx_type: TxType::Dispute,
+            amount: None,
+            ..deposit
+        };
+
+        assert!(!deduplicator.check_row(deposit).unwrap());
+        deduplicator.record_row(deposit);
+        assert!(deduplicator.check_row(deposit).unwrap());
+        // Same tx id, other transaction type
+        assert!(!deduplicator.check_row(dispute).unwrap());
+        deduplicator.hold_row(dispute);
+        assert!(deduplicator.check_row(dispute).unwrap());
+        // A rejected row can be sent again
+        deduplicator.forget_row(dispute);
+        assert!(!deduplicator.check_row(dispute).unwrap());
+
+        // Tx ids are per client
+        let other_client: Transaction = Transaction {
+            client: cid(2),
+            ..deposit
+        };
+        assert!(!deduplicator.check_row(other_client).unwrap());
+
+        let conflicting: Transaction = Transaction {
+            amount: Some(3_f32),
+            ..deposit
+        };
+        assert_eq!(
+            "PROCESSOR ERROR: Transaction 1 conflicts with an earlier deposit row with different content.",
+            deduplicator.check_row(conflicting).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let filename: String = std::env::temp_dir()
+            .join("toy_processor_dedup_state.csv")
+            .display()
+            .to_string();
+        let mut deduplicator: Deduplicator = Deduplicator::new();
+        let withdrawal: Transaction = Transaction {
+            tx_type: TxType::Withdrawal,
+            client: cid(2),
+            tx: tid(7),
+            amount: Some(1_f32),
+            timestamp: Some(100),
+        };
+        deduplicator.record_row(withdrawal);
+        assert!(!deduplicator.check_file(42));
+        deduplicator.save(&filename).unwrap();
+
+        let mut loaded: Deduplicator = Deduplicator::new();
+        loaded.load(&filename).unwrap();
+        assert!(loaded.check_row(withdrawal).unwrap());
+        assert!(loaded.check_file(42));
+    }
+}
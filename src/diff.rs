@@ -0,0 +1,262 @@
+use crate::client::Client;
+use crate::error::p_error;
+use crate::ids::ClientId;
+use crate::ledger::to_minor_units;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::io::Write;
+
+lazy_static! {
+    static ref CHANGES_HEADER: csv::ByteRecord = csv::ByteRecord::from(vec![
+        "client",
+        "available_before",
+        "available_after",
+        "held_before",
+        "held_after",
+        "total_before",
+        "total_after",
+        "locked_before",
+        "locked_after",
+    ]);
+}
+
+// Balances of a client, as printed by print_clients
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ClientState {
+    pub client: ClientId,
+    pub available: f32,
+    pub held: f32,
+    pub total: f32,
+    pub locked: bool,
+}
+
+impl From<&Client> for ClientState {
+    fn from(client: &Client) -> Self {
+        Self {
+            client: client.get_id(),
+            available: client.get_available_amount(),
+            held: client.get_held_amount(),
+            total: client.get_total_amount(),
+            locked: client.check_locked_account(),
+        }
+    }
+}
+
+// State of a client before and after, missing on the side where the
+// client doesn't exist
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClientChange {
+    pub client: ClientId,
+    pub before: Option<ClientState>,
+    pub after: Option<ClientState>,
+}
+
+impl ClientChange {
+    pub fn record(&self) -> csv::ByteRecord {
+        let amount = |state: Option<ClientState>, field: fn(&ClientState) -> f32| {
+            state
+                .map(|state| format!("{:.4}", field(&state)))
+                .unwrap_or_default()
+        };
+        let locked = |state: Option<ClientState>| {
+            state
+                .map(|state| state.locked.to_string())
+                .unwrap_or_default()
+        };
+
+        csv::ByteRecord::from(vec![
+            format!("{}", self.client),
+            amount(self.before, |state| state.available),
+            amount(self.after, |state| state.available),
+            amount(self.before, |state| state.held),
+            amount(self.after, |state| state.held),
+            amount(self.before, |state| state.total),
+            amount(self.after, |state| state.total),
+            locked(self.before),
+            locked(self.after),
+        ])
+    }
+}
+
+// Largest difference between two amounts that still counts as equal. The
+// absolute tolerance is an amount, the relative one a fraction of the
+// larger amount. The default only accepts differences below the printed
+// precision.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Tolerance {
+    pub absolute: f32,
+    pub relative: f32,
+    // Compare balances only
+    pub ignore_locked: bool,
+}
+
+impl Tolerance {
+    // Amounts are compared in ten-thousandths, as printed
+    fn same_amount(&self, before: f32, after: f32) -> bool {
+        let before: i64 = to_minor_units(before);
+        let after: i64 = to_minor_units(after);
+        let relative: f64 = f64::from(self.relative) * before.abs().max(after.abs()) as f64;
+        let allowed: i64 = to_minor_units(self.absolute).max(relative.round() as i64);
+
+        (before - after).abs() <= allowed
+    }
+
+    fn same_state(&self, before: &ClientState, after: &ClientState) -> bool {
+        self.same_amount(before.available, after.available)
+            && self.same_amount(before.held, after.held)
+            && self.same_amount(before.total, after.total)
+            && (self.ignore_locked || before.locked == after.locked)
+    }
+}
+
+// Reads an account list, as printed by print_clients. Extra columns, like
+// the ones of --client-details, are ignored.
+pub fn load_client_states(filename: &str) -> Result<Vec<ClientState>, Box<dyn Error>> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(filename)?;
+
+    let mut states: Vec<ClientState> = Vec::new();
+    let mut client_ids: HashSet<ClientId> = HashSet::new();
+    for row in csv_reader.deserialize() {
+        let state: ClientState = row?;
+
+        if !client_ids.insert(state.client) {
+            return p_error(format!(
+                "Client {} is listed more than once in {}.",
+                state.client, filename
+            ));
+        }
+
+        states.push(state);
+    }
+
+    Ok(states)
+}
+
+pub fn write_changes<W: Write>(
+    writer: &mut csv::Writer<W>,
+    changes: &[ClientChange],
+) -> Result<(), Box<dyn Error>> {
+    writer.write_byte_record(&CHANGES_HEADER)?;
+
+    for change in changes.iter() {
+        writer.write_byte_record(&change.record())?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub fn print_changes(changes: &[ClientChange]) -> Result<(), Box<dyn Error>> {
+    write_changes(&mut csv::Writer::from_writer(std::io::stdout()), changes)
+}
+
+// Clients whose state differs, ordered by client id
+pub fn diff_states(
+    before: &[ClientState],
+    after: &[ClientState],
+    tolerance: &Tolerance,
+) -> Vec<ClientChange> {
+    let mut states: BTreeMap<ClientId, (Option<ClientState>, Option<ClientState>)> =
+        BTreeMap::new();
+    for state in before {
+        states.entry(state.client).or_default().0 = Some(*state);
+    }
+    for state in after {
+        states.entry(state.client).or_default().1 = Some(*state);
+    }
+
+    states
+        .into_iter()
+        .filter(|(_, states)| match states {
+            (Some(before), Some(after)) => !tolerance.same_state(before, after),
+            _ => true,
+        })
+        .map(|(client, (before, after))| ClientChange {
+            client,
+            before,
+            after,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::cid;
+
+    fn state(client: ClientId, available: f32, locked: bool) -> ClientState {
+        ClientState {
+            client,
+            available,
+            held: 0_f32,
+            total: available,
+            locked,
+        }
+    }
+
+    #[test]
+    fn test_diff_states() {
+        let before: Vec<ClientState> =
+            vec![state(cid(1), 1_f32, false), state(cid(2), 2_f32, false)];
+        let after: Vec<ClientState> = vec![
+            state(cid(3), 3_f32, false),
+            state(cid(2), 2.00001_f32, true),
+            state(cid(1), 1.00001_f32, false),
+        ];
+
+        assert_eq!(
+            vec![
+                ClientChange {
+                    client: cid(2),
+                    before: Some(before[1]),
+                    after: Some(after[1]),
+                },
+                ClientChange {
+                    client: cid(3),
+                    before: None,
+                    after: Some(after[0]),
+                },
+            ],
+            diff_states(&before, &after, &Tolerance::default())
+        );
+        assert_eq!(
+            csv::ByteRecord::from(vec![
+                "3", "", "3.0000", "", "0.0000", "", "3.0000", "", "false"
+            ]),
+            diff_states(&before, &after, &Tolerance::default())[1].record()
+        );
+    }
+
+    #[test]
+    fn test_diff_states_with_tolerance() {
+        let before: Vec<ClientState> =
+            vec![state(cid(1), 100_f32, false), state(cid(2), 2_f32, false)];
+        let after: Vec<ClientState> = vec![
+            state(cid(1), 100.5_f32, false),
+            state(cid(2), 2.01_f32, true),
+        ];
+
+        let absolute: Tolerance = Tolerance {
+            absolute: 0.01_f32,
+            ..Tolerance::default()
+        };
+        assert_eq!(
+            vec![cid(1), cid(2)],
+            diff_states(&before, &after, &absolute)
+                .iter()
+                .map(|change| change.client)
+                .collect::<Vec<ClientId>>()
+        );
+
+        let relative: Tolerance = Tolerance {
+            relative: 0.005_f32,
+            ignore_locked: true,
+            ..Tolerance::default()
+        };
+        assert!(diff_states(&before, &after, &relative).is_empty());
+    }
+}
@@ -0,0 +1,57 @@
+use crate::error::p_error;
+use crate::transaction::TxType;
+use std::error::Error;
+use std::str::FromStr;
+
+// What happens to a dispute that is still open when its deadline passes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExpiryAction {
+    #[default]
+    Resolve,
+    Chargeback,
+}
+
+impl ExpiryAction {
+    pub fn get_tx_type(self) -> TxType {
+        match self {
+            ExpiryAction::Resolve => TxType::Resolve,
+            ExpiryAction::Chargeback => TxType::Chargeback,
+        }
+    }
+}
+
+impl FromStr for ExpiryAction {
+    type Err = Box<dyn Error>;
+
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        match action {
+            "resolve" => Ok(ExpiryAction::Resolve),
+            "chargeback" => Ok(ExpiryAction::Chargeback),
+            _ => p_error(format!(
+                "Unknown dispute expiry action {}. Use resolve or chargeback.",
+                action
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expiry_action_from_str() {
+        assert_eq!(
+            ExpiryAction::Resolve,
+            "resolve".parse::<ExpiryAction>().unwrap()
+        );
+        assert_eq!(
+            TxType::Chargeback,
+            "chargeback".parse::<ExpiryAction>().unwrap().get_tx_type()
+        );
+        assert_eq!(
+            "PROCESSOR ERROR: Unknown dispute expiry action refund. Use resolve or chargeback.",
+            "refund".parse::<ExpiryAction>().unwrap_err().to_string()
+        );
+    }
+}
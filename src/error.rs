@@ -12,6 +12,6 @@ impl fmt::Display for ProcessorError {
 
 impl Error for ProcessorError {}
 
-pub fn p_error(error_msg: String) -> Result<(), Box<dyn Error>> {
+pub fn p_error<T>(error_msg: String) -> Result<T, Box<dyn Error>> {
     Err(Box::new(ProcessorError(error_msg)))
 }
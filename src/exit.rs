@@ -0,0 +1,20 @@
+// Exit codes of the toy_processor binary, listed in the README so
+// schedulers can tell a partial failure from a clean run
+
+// Every row was processed
+pub const SUCCESS: i32 = 0;
+// Verification found issues, or diff found clients that differ
+pub const CHECK_FAILED: i32 = 1;
+// Invalid arguments or option values, also used by clap and for input
+// files with an invalid header
+pub const USAGE_ERROR: i32 = 2;
+// A file couldn't be read, parsed or written
+pub const IO_ERROR: i32 = 3;
+// The input was processed, but some rows were rejected
+pub const REJECTED_ROWS: i32 = 4;
+// Aborted by --on-error abort
+pub const ABORTED_ON_ERROR: i32 = 5;
+// Aborted by --max-errors
+pub const ABORTED_MAX_ERRORS: i32 = 6;
+// Aborted by --max-error-rate
+pub const ABORTED_MAX_ERROR_RATE: i32 = 7;
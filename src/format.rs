@@ -0,0 +1,32 @@
+use crate::error::p_error;
+use std::error::Error;
+use std::str::FromStr;
+
+// Layout of the transaction input and of the account output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Csv,
+    Tsv,
+}
+
+impl Format {
+    pub fn get_delimiter(self) -> u8 {
+        match self {
+            Format::Csv => b',',
+            Format::Tsv => b'\t',
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = Box<dyn Error>;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            _ => p_error(format!("Unknown format {}. Use csv or tsv.", format)),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use crate::error::p_error;
+use serde::{Deserialize, Deserializer};
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+// Client and tx ids are u16 and u32 by default, which keeps a Transaction
+// at 32 bytes. The `wide-ids` feature widens both to u64 and the
+// `string-ids` feature accepts any text up to SHORT_ID_CAPACITY bytes.
+#[cfg(not(any(feature = "wide-ids", feature = "string-ids")))]
+pub type ClientId = u16;
+#[cfg(not(any(feature = "wide-ids", feature = "string-ids")))]
+pub type TxId = u32;
+
+#[cfg(all(feature = "wide-ids", not(feature = "string-ids")))]
+pub type ClientId = u64;
+#[cfg(all(feature = "wide-ids", not(feature = "string-ids")))]
+pub type TxId = u64;
+
+#[cfg(feature = "string-ids")]
+pub type ClientId = ShortId;
+#[cfg(feature = "string-ids")]
+pub type TxId = ShortId;
+
+#[cfg(all(feature = "wide-ids", feature = "string-ids"))]
+compile_error!("The `wide-ids` and `string-ids` features can't be enabled together.");
+
+// Test fixtures number their ids, and build them through FromStr so they
+// compile with every id type
+#[cfg(test)]
+pub fn cid(id: u64) -> ClientId {
+    id.to_string().parse().unwrap()
+}
+
+#[cfg(test)]
+pub fn tid(id: u64) -> TxId {
+    id.to_string().parse().unwrap()
+}
+
+pub const SHORT_ID_CAPACITY: usize = 23;
+
+/*******************************
+< ShortId >
+
+Text id stored inline, so it stays Copy like the integer ids and
+transactions don't allocate. Ids are compared and ordered as text.
+
+*******************************/
+#[derive(Clone, Copy)]
+pub struct ShortId {
+    len: u8,
+    bytes: [u8; SHORT_ID_CAPACITY],
+}
+
+impl ShortId {
+    pub fn as_str(&self) -> &str {
+        // Only built from a &str, so the bytes are valid UTF-8
+        std::str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or_default()
+    }
+}
+
+impl FromStr for ShortId {
+    type Err = Box<dyn Error>;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        if id.is_empty() {
+            return p_error("Ids can't be empty.".to_string());
+        }
+        if id.len() > SHORT_ID_CAPACITY {
+            return p_error(format!(
+                "Id {} is longer than {} bytes.",
+                id, SHORT_ID_CAPACITY
+            ));
+        }
+
+        let mut bytes: [u8; SHORT_ID_CAPACITY] = [0; SHORT_ID_CAPACITY];
+        bytes[..id.len()].copy_from_slice(id.as_bytes());
+
+        Ok(Self {
+            len: id.len() as u8,
+            bytes,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for ShortId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id: String = String::deserialize(deserializer)?;
+        id.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl PartialEq for ShortId {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for ShortId {}
+
+impl Hash for ShortId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl Ord for ShortId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl PartialOrd for ShortId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for ShortId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl fmt::Debug for ShortId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_id() {
+        let id: ShortId = "merchant-7".parse().unwrap();
+        assert_eq!("merchant-7", id.to_string());
+        assert_eq!(id, "merchant-7".parse().unwrap());
+        assert!(id < "merchant-8".parse().unwrap());
+        assert_eq!(24, std::mem::size_of::<ShortId>());
+
+        assert_eq!(
+            "PROCESSOR ERROR: Id abcdefghijklmnopqrstuvwxyz is longer than 23 bytes.",
+            "abcdefghijklmnopqrstuvwxyz"
+                .parse::<ShortId>()
+                .unwrap_err()
+                .to_string()
+        );
+        assert!("".parse::<ShortId>().is_err());
+    }
+
+    #[test]
+    fn test_deserialize_short_id() {
+        let mut reader = csv::Reader::from_reader("client\nacme\n".as_bytes());
+        let ids: Vec<ShortId> = reader
+            .deserialize::<(ShortId,)>()
+            .map(|row| row.unwrap().0)
+            .collect();
+
+        assert_eq!(vec!["acme".parse::<ShortId>().unwrap()], ids);
+    }
+}
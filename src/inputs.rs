@@ -0,0 +1,173 @@
+use crate::error::p_error;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+// Order in which several input files are processed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputOrder {
+    // By path
+    #[default]
+    Lexical,
+    // By modification time, oldest first
+    Mtime,
+}
+
+impl FromStr for InputOrder {
+    type Err = Box<dyn Error>;
+
+    fn from_str(order: &str) -> Result<Self, Self::Err> {
+        match order {
+            "lexical" => Ok(InputOrder::Lexical),
+            "mtime" => Ok(InputOrder::Mtime),
+            _ => p_error(format!(
+                "Unknown input order {}. Use lexical or mtime.",
+                order
+            )),
+        }
+    }
+}
+
+// Files of a directory, or matching a glob pattern, or the file itself
+fn expand(input: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let path: &Path = Path::new(input);
+
+    if path.is_dir() {
+        let mut files: Vec<PathBuf> = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let file: PathBuf = entry?.path();
+            if file.is_file() {
+                files.push(file);
+            }
+        }
+        return Ok(files);
+    }
+
+    if path.exists() || !input.contains(['*', '?', '[']) {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files: Vec<PathBuf> = Vec::new();
+    for entry in glob::glob(input)? {
+        let file: PathBuf = entry?;
+        if file.is_file() {
+            files.push(file);
+        }
+    }
+    if files.is_empty() {
+        return p_error(format!("Pattern {} doesn't match any file.", input));
+    }
+
+    Ok(files)
+}
+
+fn modified(file: &Path) -> Result<SystemTime, Box<dyn Error>> {
+    Ok(fs::metadata(file)?.modified()?)
+}
+
+// Expands directories and glob patterns and sorts the files. A file given
+// more than once is processed once.
+pub fn resolve_inputs(inputs: &[String], order: InputOrder) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for input in inputs {
+        files.extend(expand(input)?);
+    }
+    files.sort();
+    files.dedup();
+
+    if order == InputOrder::Mtime {
+        let mut dated: Vec<(SystemTime, PathBuf)> = Vec::new();
+        for file in files {
+            dated.push((modified(&file)?, file));
+        }
+        // Stable, so files with the same time keep the lexical order
+        dated.sort_by_key(|(time, _)| *time);
+        files = dated.into_iter().map(|(_, file)| file).collect();
+    }
+
+    Ok(files
+        .into_iter()
+        .map(|file| file.display().to_string())
+        .collect())
+}
+
+// Reads the files listed in a manifest, one per line, in the listed order.
+// Relative paths are relative to the manifest, empty lines and lines
+// starting with # are skipped.
+pub fn load_manifest(filename: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let directory: &Path = Path::new(filename).parent().unwrap_or(Path::new(""));
+
+    Ok(fs::read_to_string(filename)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| directory.join(line).display().to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_inputs() {
+        let shards: String = format!("{}/tests/resources/shards", env!("CARGO_MANIFEST_DIR"));
+        let names = |files: Vec<String>| -> Vec<String> {
+            files
+                .iter()
+                .map(|file| file.rsplit('/').next().unwrap().to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            vec!["01.csv", "02.csv", "03.csv"],
+            names(resolve_inputs(std::slice::from_ref(&shards), InputOrder::Lexical).unwrap())
+        );
+        assert_eq!(
+            vec!["01.csv", "03.csv"],
+            names(
+                resolve_inputs(
+                    &[
+                        format!("{}/0[13].csv", shards),
+                        format!("{}/01.csv", shards)
+                    ],
+                    InputOrder::Lexical
+                )
+                .unwrap()
+            )
+        );
+        assert_eq!(
+            format!(
+                "PROCESSOR ERROR: Pattern {}/*.zip doesn't match any file.",
+                shards
+            ),
+            resolve_inputs(&[format!("{}/*.zip", shards)], InputOrder::Lexical)
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_load_manifest() {
+        let manifest: String = format!(
+            "{}/tests/resources/shards.manifest",
+            env!("CARGO_MANIFEST_DIR")
+        );
+
+        assert_eq!(
+            vec![
+                format!(
+                    "{}/tests/resources/shards/02.csv",
+                    env!("CARGO_MANIFEST_DIR")
+                ),
+                format!(
+                    "{}/tests/resources/shards/01.csv",
+                    env!("CARGO_MANIFEST_DIR")
+                ),
+            ],
+            load_manifest(&manifest).unwrap()
+        );
+    }
+}
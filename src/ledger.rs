@@ -0,0 +1,229 @@
+use crate::error::p_error;
+use crate::ids::{ClientId, TxId};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+// Amounts are tracked with 4 decimal places, so every posting is stored
+// in ten-thousandths to keep the journal exact
+pub const MINOR_UNITS: f64 = 10_000_f64;
+
+pub fn to_minor_units(amount: f32) -> i64 {
+    (amount as f64 * MINOR_UNITS).round() as i64
+}
+
+pub fn from_minor_units(amount: i64) -> f64 {
+    amount as f64 / MINOR_UNITS
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Account {
+    ClientAvailable(ClientId),
+    ClientHeld(ClientId),
+    BankClearing,
+    ChargebackLoss,
+    OpeningBalance,
+}
+
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Account::ClientAvailable(id) => write!(f, "client:{}:available", id),
+            Account::ClientHeld(id) => write!(f, "client:{}:held", id),
+            Account::BankClearing => write!(f, "bank:clearing"),
+            Account::ChargebackLoss => write!(f, "bank:chargeback_loss"),
+            Account::OpeningBalance => write!(f, "bank:opening_balance"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Posting {
+    // Opening balances don't belong to any transaction
+    pub tx: Option<TxId>,
+    pub account: Account,
+    pub amount: i64,
+}
+
+/*******************************
+< Ledger >
+
+Double-entry journal. Every call to post() must be balanced, meaning the
+lines of a single entry sum up to zero. Increases of an account are
+positive amounts, decreases are negative amounts.
+
+*******************************/
+#[derive(Debug, Clone, Default)]
+pub struct Ledger {
+    postings: Vec<Posting>,
+    balances: HashMap<Account, i64>,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn post(
+        &mut self,
+        tx: Option<TxId>,
+        lines: &[(Account, i64)],
+    ) -> Result<(), Box<dyn Error>> {
+        let sum: i64 = lines.iter().map(|(_, amount)| amount).sum();
+        if sum != 0 {
+            let entry: String = match tx {
+                Some(tx) => format!("transaction {}", tx),
+                None => "opening balance".to_string(),
+            };
+            return p_error(format!(
+                "Unbalanced journal entry for {}. Lines sum up to {}.",
+                entry,
+                from_minor_units(sum)
+            ));
+        }
+
+        for (account, amount) in lines {
+            self.postings.push(Posting {
+                tx,
+                account: *account,
+                amount: *amount,
+            });
+            *self.balances.entry(*account).or_insert(0) += amount;
+        }
+
+        Ok(())
+    }
+
+    pub fn get_postings(&self) -> &[Posting] {
+        &self.postings
+    }
+
+    pub fn get_balance(&self, account: Account) -> i64 {
+        self.balances.get(&account).copied().unwrap_or(0)
+    }
+
+    // Balances of all accounts touched by the journal, ordered by account
+    pub fn trial_balance(&self) -> Vec<(Account, i64)> {
+        let mut balances: Vec<(Account, i64)> = self
+            .balances
+            .iter()
+            .map(|(account, amount)| (*account, *amount))
+            .collect();
+        balances.sort();
+        balances
+    }
+
+    // Merge the balances of another journal into this one
+    pub fn merge_balances(&mut self, other: &Ledger) {
+        for (account, amount) in other.balances.iter() {
+            *self.balances.entry(*account).or_insert(0) += amount;
+        }
+    }
+
+    // Every entry is balanced, so all postings and all account balances
+    // have to sum up to zero
+    pub fn check_invariant(&self) -> Result<(), Box<dyn Error>> {
+        let postings_sum: i64 = self.postings.iter().map(|posting| posting.amount).sum();
+        let balances_sum: i64 = self.balances.values().sum();
+
+        if postings_sum != 0 || balances_sum != 0 {
+            return p_error(format!(
+                "Ledger is out of balance. Postings sum up to {}, account balances sum up to {}.",
+                from_minor_units(postings_sum),
+                from_minor_units(balances_sum)
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::{cid, tid};
+
+    #[test]
+    fn test_minor_units() {
+        assert_eq!(to_minor_units(10.0456_f32), 100456);
+        assert_eq!(to_minor_units(10.001_f32), 100010);
+        assert_eq!(from_minor_units(15000), 1.5_f64);
+    }
+
+    #[test]
+    fn test_balanced_posting() {
+        let mut ledger: Ledger = Ledger::new();
+
+        assert_eq!(
+            (),
+            ledger
+                .post(
+                    Some(tid(1)),
+                    &[
+                        (Account::ClientAvailable(cid(1)), 200),
+                        (Account::BankClearing, -200)
+                    ]
+                )
+                .unwrap()
+        );
+        assert_eq!(
+            (),
+            ledger
+                .post(
+                    Some(tid(2)),
+                    &[
+                        (Account::ClientAvailable(cid(1)), -50),
+                        (Account::ClientHeld(cid(1)), 50)
+                    ]
+                )
+                .unwrap()
+        );
+
+        assert_eq!(150, ledger.get_balance(Account::ClientAvailable(cid(1))));
+        assert_eq!(50, ledger.get_balance(Account::ClientHeld(cid(1))));
+        assert_eq!(-200, ledger.get_balance(Account::BankClearing));
+        assert_eq!(0, ledger.get_balance(Account::ChargebackLoss));
+        assert_eq!(4, ledger.get_postings().len());
+        assert_eq!(
+            vec![
+                (Account::ClientAvailable(cid(1)), 150),
+                (Account::ClientHeld(cid(1)), 50),
+                (Account::BankClearing, -200)
+            ],
+            ledger.trial_balance()
+        );
+        assert_eq!((), ledger.check_invariant().unwrap());
+
+        // Postings that skipped post() can leave the journal out of balance
+        ledger.postings.push(Posting {
+            tx: Some(tid(3)),
+            account: Account::ChargebackLoss,
+            amount: 10,
+        });
+        assert_eq!(
+            "PROCESSOR ERROR: Ledger is out of balance. Postings sum up to 0.001, account balances sum up to 0.",
+            ledger.check_invariant().unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_unbalanced_posting() {
+        let mut ledger: Ledger = Ledger::new();
+
+        assert_eq!(
+            "PROCESSOR ERROR: Unbalanced journal entry for transaction 1. Lines sum up to 0.01.",
+            ledger
+                .post(
+                    Some(tid(1)),
+                    &[
+                        (Account::ClientAvailable(cid(1)), 200),
+                        (Account::BankClearing, -100)
+                    ]
+                )
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(0, ledger.get_postings().len());
+        assert_eq!((), ledger.check_invariant().unwrap());
+    }
+}
@@ -1,7 +1,27 @@
 #[macro_use]
 extern crate lazy_static;
 
+pub mod batch;
+pub mod budget;
 pub mod client;
+pub mod columns;
+pub mod compression;
+pub mod config;
+pub mod deadlines;
+pub mod dedup;
+pub mod diff;
+pub mod dispute;
 pub mod error;
+pub mod exit;
+pub mod format;
+pub mod ids;
+pub mod inputs;
+pub mod ledger;
+pub mod location;
+pub mod policy;
 pub mod processor;
+pub mod reorder;
+pub mod replay;
+pub mod rules;
+pub mod summary;
 pub mod transaction;
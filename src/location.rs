@@ -0,0 +1,77 @@
+use std::fmt;
+
+/*******************************
+< RowLocation >
+
+Where an input row was read: file, line number and byte offset of the
+row, with its raw text so a rejected row can be found and fixed.
+
+*******************************/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowLocation {
+    pub file: String,
+    pub line: u64,
+    pub byte: u64,
+    // Fields joined with the input delimiter, as they were read
+    pub row: String,
+}
+
+impl RowLocation {
+    pub fn new(file: &str, record: &csv::ByteRecord, delimiter: u8) -> Self {
+        let (line, byte): (u64, u64) = record
+            .position()
+            .map_or((0, 0), |position| (position.line(), position.byte()));
+        let row: Vec<u8> = record.iter().collect::<Vec<&[u8]>>().join(&delimiter);
+
+        Self {
+            file: file.to_string(),
+            line,
+            byte,
+            row: String::from_utf8_lossy(&row).to_string(),
+        }
+    }
+
+    // Location of a row the reader couldn't split into fields
+    pub fn from_error(file: &str, error: &csv::Error) -> Self {
+        let (line, byte): (u64, u64) = error
+            .position()
+            .map_or((0, 0), |position| (position.line(), position.byte()));
+
+        Self {
+            file: file.to_string(),
+            line,
+            byte,
+            row: String::new(),
+        }
+    }
+}
+
+impl fmt::Display for RowLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{} (byte {})", self.file, self.line, self.byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_location() {
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader("type,client,tx\ndeposit, 1,1,2.0\ndispute,1\n".as_bytes());
+        let records: Vec<csv::ByteRecord> = reader
+            .byte_records()
+            .map(|record| record.unwrap())
+            .collect();
+
+        let location: RowLocation = RowLocation::new("input.csv", &records[1], b',');
+        assert_eq!("input.csv:3 (byte 32)", location.to_string());
+        assert_eq!("dispute,1", location.row);
+        assert_eq!(
+            "deposit, 1,1,2.0",
+            RowLocation::new("input.csv", &records[0], b',').row
+        );
+    }
+}
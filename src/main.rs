@@ -1,33 +1,766 @@
-use std::env;
-use toy_processor::processor::Processor;
+use clap::{Args, Parser, Subcommand};
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+use toy_processor::batch::Atomicity;
+use toy_processor::budget::{AbortReason, ErrorBudget, OnError};
+use toy_processor::columns::ColumnAliases;
+use toy_processor::config::{
+    load_client_configs, load_opening_balances, ClientConfig, OpeningBalance,
+};
+use toy_processor::deadlines::{DeadlineUnit, Deadlines};
+use toy_processor::diff::{
+    diff_states, load_client_states, write_changes, ClientChange, ClientState, Tolerance,
+};
+use toy_processor::dispute::ExpiryAction;
+use toy_processor::exit;
+use toy_processor::format::Format;
+use toy_processor::inputs::{load_manifest, resolve_inputs, InputOrder};
+use toy_processor::policy::{load_policies, LockPolicy};
+use toy_processor::processor::{DryRun, Processor};
+use toy_processor::replay::{Replayer, DEFAULT_CHECKPOINT_INTERVAL};
+use toy_processor::rules::{load_rules, Rule};
+use toy_processor::summary::RunSummary;
+use toy_processor::transaction::{TxType, UnexpectedAmount};
 
-fn main() {
-    // Process arguments
-    let args: Vec<String> = env::args().collect();
+/// Processes deposits, withdrawals, disputes, resolves and chargebacks from
+/// CSV files and prints the resulting client accounts.
+///
+/// Giving only the input file, with or without options, is the same as the
+/// process command.
+#[derive(Parser)]
+#[command(
+    name = "toy_processor",
+    version,
+    args_conflicts_with_subcommands = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    process: ProcessArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Process the input and print the client accounts
+    Process(ProcessArgs),
+    /// Process the input and check every account against its ledger
+    Verify(ProcessArgs),
+    /// Compare two account lists
+    Diff(DiffArgs),
+    /// Process the input and print run statistics instead of the accounts
+    Stats(ProcessArgs),
+    /// Print the accounts as of an input row or timestamp
+    Replay(ProcessArgs),
+}
+
+#[derive(Args)]
+struct ProcessArgs {
+    /// Transactions files, directories or glob patterns, processed as one stream
+    #[arg(value_name = "INPUT")]
+    input: Vec<String>,
+
+    /// Order of the input files: lexical or mtime
+    #[arg(long, value_name = "ORDER", default_value = "lexical", value_parser = parse_order)]
+    order: InputOrder,
+
+    /// File listing the input files in processing order, one per line
+    #[arg(long, value_name = "FILE")]
+    manifest: Option<String>,
+
+    /// Layout of the input file: csv or tsv
+    #[arg(long, value_name = "FORMAT", default_value = "csv", value_parser = parse_format)]
+    input_format: Format,
+
+    /// Input files have no header, columns are type, client, tx, amount and timestamp
+    #[arg(long)]
+    headerless: bool,
+
+    /// Amounts of dispute, resolve and chargeback rows: reject the row or ignore the amount
+    #[arg(long, value_name = "ACTION", default_value = "reject", value_parser = parse_unexpected_amount)]
+    on_unexpected_amount: UnexpectedAmount,
+
+    /// Extra header names of the input columns (alias,column)
+    #[arg(long, value_name = "FILE")]
+    column_aliases: Option<String>,
+
+    /// Layout of the output: csv or tsv
+    #[arg(long, value_name = "FORMAT", default_value = "csv", value_parser = parse_format)]
+    output_format: Format,
+
+    /// Write the output to a file instead of STDOUT
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<String>,
+
+    /// Write the rejected rows, with the reason, to a CSV file
+    #[arg(long, value_name = "FILE")]
+    rejects: Option<String>,
+
+    /// Check every account against its ledger after processing
+    #[arg(long)]
+    verify: bool,
+
+    /// Client registry (client,name,status,credit_limit,initial_balance)
+    #[arg(long, value_name = "FILE")]
+    client_config: Option<String>,
+
+    /// Reject transactions of clients missing from the client registry
+    #[arg(long)]
+    strict_clients: bool,
+
+    /// Add the name and status columns to the account list
+    #[arg(long)]
+    client_details: bool,
+
+    /// State file with the opening balances of the clients, in the output layout
+    #[arg(long, value_name = "FILE")]
+    opening_balances: Option<String>,
+
+    /// State file with the fingerprints of the rows and files already ingested
+    #[arg(long, value_name = "FILE")]
+    dedup_state: Option<String>,
+
+    /// Velocity and fraud rules (rule,action,limit,window)
+    #[arg(long, value_name = "FILE")]
+    rules: Option<String>,
+
+    /// Write the triggered rules to a CSV file instead of STDERR
+    #[arg(long, value_name = "FILE")]
+    alerts: Option<String>,
+
+    /// Lock policies (policy,limit)
+    #[arg(long, value_name = "FILE")]
+    policy: Option<String>,
+
+    /// Write the dispute and chargeback counters of every client to a CSV file
+    #[arg(long, value_name = "FILE")]
+    risk_report: Option<String>,
+
+    /// Write the clients that went into overdraft to a CSV file
+    #[arg(long, value_name = "FILE")]
+    overdraft_report: Option<String>,
+
+    /// Deadline of every dispute, in rows or seconds
+    #[arg(long, value_name = "LENGTH")]
+    dispute_deadline: Option<u64>,
+
+    /// Unit of the dispute deadline: rows or seconds
+    #[arg(long, value_name = "UNIT")]
+    dispute_deadline_unit: Option<String>,
+
+    /// What happens to an expired dispute: resolve or chargeback
+    #[arg(long, value_name = "ACTION")]
+    on_dispute_expiry: Option<String>,
+
+    /// Expiry of every authorization, in rows or seconds
+    #[arg(long, value_name = "LENGTH")]
+    authorization_expiry: Option<u64>,
+
+    /// Unit of the authorization expiry: rows or seconds
+    #[arg(long, value_name = "UNIT")]
+    authorization_expiry_unit: Option<String>,
+
+    /// Write the events generated by expired disputes and authorizations to a CSV file instead of STDERR
+    #[arg(long, value_name = "FILE")]
+    synthetic_events: Option<String>,
+
+    /// Reorder rows to event-time order within a window of rows
+    #[arg(long, value_name = "ROWS")]
+    reorder_window: Option<usize>,
+
+    /// Print the accounts as they were after the given input row
+    #[arg(long, value_name = "ROW")]
+    as_of_row: Option<u64>,
+
+    /// Print the accounts as they were after the last row up to the given timestamp
+    #[arg(long, value_name = "TIMESTAMP")]
+    as_of_timestamp: Option<u64>,
+
+    /// Print the changes the input would make, without keeping them
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Apply rows all-or-nothing per input file or per batch column value: row, file or batch
+    #[arg(long, value_name = "GROUP", default_value = "row", value_parser = parse_atomicity)]
+    atomic: Atomicity,
+
+    /// What to do with a rejected row: continue or abort without any output
+    #[arg(long, value_name = "ACTION", default_value = "continue", value_parser = parse_on_error)]
+    on_error: OnError,
+
+    /// Abort without any output when more rows are rejected
+    #[arg(long, value_name = "N")]
+    max_errors: Option<u64>,
+
+    /// Abort without any output when a larger fraction of the rows is rejected
+    #[arg(long, value_name = "P")]
+    max_error_rate: Option<f64>,
+
+    /// Report the number of rows read, rejected and skipped on STDERR
+    #[arg(long)]
+    summary: bool,
+}
+
+#[derive(Args)]
+struct DiffArgs {
+    /// Account list to compare from
+    before: String,
+
+    /// Account list to compare to
+    after: String,
+
+    /// Largest accepted difference between two amounts
+    #[arg(long, value_name = "AMOUNT", default_value_t = 0_f32)]
+    tolerance: f32,
 
-    if args.len() > 2 {
-        eprintln!("There should be only one argument given to the program.");
-        std::process::exit(1);
+    /// Largest accepted difference, as a fraction of the larger amount
+    #[arg(long, value_name = "FRACTION", default_value_t = 0_f32)]
+    relative_tolerance: f32,
+
+    /// Compare balances only
+    #[arg(long)]
+    ignore_locked: bool,
+}
+
+// What a processing command prints after the input is processed
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Process,
+    Verify,
+    Stats,
+    Replay,
+}
+
+fn parse_format(format: &str) -> Result<Format, String> {
+    format
+        .parse()
+        .map_err(|err: Box<dyn Error>| err.to_string())
+}
+
+fn parse_order(order: &str) -> Result<InputOrder, String> {
+    order.parse().map_err(|err: Box<dyn Error>| err.to_string())
+}
+
+fn parse_atomicity(atomicity: &str) -> Result<Atomicity, String> {
+    atomicity
+        .parse()
+        .map_err(|err: Box<dyn Error>| err.to_string())
+}
+
+fn parse_on_error(action: &str) -> Result<OnError, String> {
+    action
+        .parse()
+        .map_err(|err: Box<dyn Error>| err.to_string())
+}
+
+fn parse_unexpected_amount(action: &str) -> Result<UnexpectedAmount, String> {
+    action
+        .parse()
+        .map_err(|err: Box<dyn Error>| err.to_string())
+}
+
+// Dispute deadlines and authorization expiries are measured in rows by
+// default
+fn deadline_unit(unit: Option<&str>, deadline: &str) -> DeadlineUnit {
+    match unit {
+        None | Some("rows") => DeadlineUnit::Rows,
+        Some("seconds") => DeadlineUnit::Seconds,
+        Some(unit) => {
+            eprintln!("Unknown {} unit {}. Use rows or seconds.", deadline, unit);
+            std::process::exit(exit::USAGE_ERROR);
+        }
+    }
+}
+
+// Exits with the code of the error budget condition that stopped the run
+fn abort(reason: AbortReason) -> ! {
+    eprintln!("ABORTED: {}", reason);
+    std::process::exit(reason.get_exit_code());
+}
+
+fn open_output(output: Option<&str>, format: Format) -> csv::Writer<Box<dyn Write>> {
+    output_writer(output, format).unwrap_or_else(|err| {
+        eprintln!(
+            "Error when trying to open output: {}, {}",
+            output.unwrap_or("STDOUT"),
+            err
+        );
+        std::process::exit(exit::IO_ERROR);
+    })
+}
+
+fn output_writer(
+    output: Option<&str>,
+    format: Format,
+) -> Result<csv::Writer<Box<dyn Write>>, Box<dyn Error>> {
+    let writer: Box<dyn Write> = match output {
+        Some(filename) => Box::new(File::create(filename)?),
+        None => Box::new(std::io::stdout()),
+    };
+
+    Ok(csv::WriterBuilder::new()
+        .delimiter(format.get_delimiter())
+        .from_writer(writer))
+}
+
+// Compares two account lists, e.g.
+//
+// toy_processor diff old.csv new.csv --tolerance 0.01
+fn diff_command(args: DiffArgs) {
+    if !args.tolerance.is_finite()
+        || args.tolerance < 0_f32
+        || !args.relative_tolerance.is_finite()
+        || args.relative_tolerance < 0_f32
+    {
+        eprintln!("Tolerances have to be positive numbers.");
+        std::process::exit(exit::USAGE_ERROR);
+    }
+    let tolerance: Tolerance = Tolerance {
+        absolute: args.tolerance,
+        relative: args.relative_tolerance,
+        ignore_locked: args.ignore_locked,
+    };
+
+    let mut states: Vec<Vec<ClientState>> = Vec::new();
+    for filename in [&args.before, &args.after] {
+        states.push(load_client_states(filename).unwrap_or_else(|err| {
+            eprintln!("Error when trying to read accounts: {}, {}", filename, err);
+            std::process::exit(exit::IO_ERROR);
+        }));
+    }
+
+    let changes: Vec<ClientChange> = diff_states(&states[0], &states[1], &tolerance);
+    if let Err(error) = write_changes(&mut csv::Writer::from_writer(std::io::stdout()), &changes) {
+        eprintln!("{}", error);
     }
+    if !changes.is_empty() {
+        eprintln!("{} client(s) differ.", changes.len());
+        std::process::exit(exit::CHECK_FAILED);
+    }
+}
+
+// Run statistics, as metric,value rows
+fn write_stats<W: Write>(
+    writer: &mut csv::Writer<W>,
+    toy_processor: &Processor,
+) -> Result<(), Box<dyn Error>> {
+    let summary: RunSummary = toy_processor.get_summary();
+    let states: Vec<ClientState> = toy_processor.get_client_states();
+    let sum = |amount: fn(&ClientState) -> f32| -> String {
+        format!("{:.4}", states.iter().map(amount).sum::<f32>())
+    };
+
+    writer.write_record(["metric", "value"])?;
+    writer.write_record(["rows", &summary.rows.to_string()])?;
+    writer.write_record(["rejected", &summary.rejected.to_string()])?;
+    writer.write_record(["duplicate_rows", &summary.duplicate_rows.to_string()])?;
+    writer.write_record(["duplicate_files", &summary.duplicate_files.to_string()])?;
+    writer.write_record(["clients", &states.len().to_string()])?;
+    writer.write_record([
+        "locked_clients",
+        &states
+            .iter()
+            .filter(|state| state.locked)
+            .count()
+            .to_string(),
+    ])?;
+    writer.write_record(["available", &sum(|state| state.available)])?;
+    writer.write_record(["held", &sum(|state| state.held)])?;
+    writer.write_record(["total", &sum(|state| state.total)])?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn process_command(options: ProcessArgs, mode: Mode) {
+    let filenames: Vec<String> = match options.manifest.as_deref() {
+        Some(_) if !options.input.is_empty() => {
+            eprintln!("Option --manifest can't be used with input files.");
+            std::process::exit(exit::USAGE_ERROR);
+        }
+        Some(manifest) => load_manifest(manifest).unwrap_or_else(|err| {
+            eprintln!("Error when trying to read manifest: {}, {}", manifest, err);
+            std::process::exit(exit::IO_ERROR);
+        }),
+        None if options.input.is_empty() => {
+            eprintln!("Error! No argument provided.");
+            std::process::exit(exit::USAGE_ERROR);
+        }
+        None => resolve_inputs(&options.input, options.order).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(exit::IO_ERROR);
+        }),
+    };
 
-    let filename: String = match args.get(1) {
-        Some(file) => file.to_string(),
+    for filename in filenames.iter() {
+        if !std::path::Path::new(filename).exists() {
+            eprintln!("File {} does not exist.", filename);
+            std::process::exit(exit::IO_ERROR);
+        }
+    }
+    let filename: String = match filenames.first() {
+        Some(filename) => filename.clone(),
         None => {
             eprintln!("Error! No argument provided.");
-            std::process::exit(1);
+            std::process::exit(exit::USAGE_ERROR);
         }
     };
 
-    if !std::path::Path::new(&filename).exists() {
-        eprintln!("File {} does not exist.", filename);
-        std::process::exit(1);
+    if options.strict_clients && options.client_config.is_none() {
+        eprintln!("Option --strict-clients requires --client-config.");
+        std::process::exit(exit::USAGE_ERROR);
+    }
+
+    if options.as_of_row.is_some() && options.as_of_timestamp.is_some() {
+        eprintln!("Options --as-of-row and --as-of-timestamp can't be used together.");
+        std::process::exit(exit::USAGE_ERROR);
+    }
+
+    if let Some(max_error_rate) = options.max_error_rate {
+        if !(0_f64..=1_f64).contains(&max_error_rate) {
+            eprintln!("Option --max-error-rate has to be between 0 and 1.");
+            std::process::exit(exit::USAGE_ERROR);
+        }
+    }
+
+    if options.dry_run && (options.as_of_row.is_some() || options.as_of_timestamp.is_some()) {
+        eprintln!("Option --dry-run can't be used with --as-of-row or --as-of-timestamp.");
+        std::process::exit(exit::USAGE_ERROR);
     }
 
-    let mut toy_processor: Processor = Processor::new(filename);
+    if options.atomic != Atomicity::Row
+        && (options.as_of_row.is_some()
+            || options.as_of_timestamp.is_some()
+            || options.reorder_window.is_some())
+    {
+        eprintln!(
+            "Option --atomic can't be used with --as-of-row, --as-of-timestamp or --reorder-window."
+        );
+        std::process::exit(exit::USAGE_ERROR);
+    }
 
-    toy_processor.process_transactions();
+    if mode == Mode::Replay && options.as_of_row.is_none() && options.as_of_timestamp.is_none() {
+        eprintln!("Command replay requires --as-of-row or --as-of-timestamp.");
+        std::process::exit(exit::USAGE_ERROR);
+    }
+
+    if filenames.len() > 1 && (options.as_of_row.is_some() || options.as_of_timestamp.is_some()) {
+        eprintln!("Options --as-of-row and --as-of-timestamp work on a single input file.");
+        std::process::exit(exit::USAGE_ERROR);
+    }
+
+    let mut toy_processor: Processor = Processor::new(filename.clone());
+    toy_processor.set_filenames(filenames);
+    toy_processor.set_strict_clients(options.strict_clients);
+    toy_processor.set_input_format(options.input_format);
+    toy_processor.set_headerless(options.headerless);
+    toy_processor.set_unexpected_amount(options.on_unexpected_amount);
+    toy_processor.set_atomicity(options.atomic);
+    toy_processor.set_error_budget(ErrorBudget {
+        on_error: options.on_error,
+        max_errors: options.max_errors,
+        max_error_rate: options.max_error_rate,
+    });
+
+    if let Some(column_aliases) = options.column_aliases {
+        let mut aliases: ColumnAliases = ColumnAliases::default();
+        if let Err(err) = aliases.load(&column_aliases) {
+            eprintln!(
+                "Error when trying to read column aliases: {}, {}",
+                column_aliases, err
+            );
+            std::process::exit(exit::IO_ERROR);
+        }
+        toy_processor.set_column_aliases(aliases);
+    }
+
+    if let Some(client_config) = options.client_config {
+        let configs: Vec<ClientConfig> =
+            load_client_configs(&client_config).unwrap_or_else(|err| {
+                eprintln!(
+                    "Error when trying to read client config: {}, {}",
+                    client_config, err
+                );
+                std::process::exit(exit::IO_ERROR);
+            });
+        if let Err(error) = toy_processor.apply_client_configs(&configs) {
+            eprintln!("{}", error);
+            std::process::exit(exit::IO_ERROR);
+        }
+    }
+
+    if let Some(opening_balances) = options.opening_balances {
+        let balances: Vec<OpeningBalance> = load_opening_balances(&opening_balances)
+            .unwrap_or_else(|err| {
+                eprintln!(
+                    "Error when trying to read opening balances: {}, {}",
+                    opening_balances, err
+                );
+                std::process::exit(exit::IO_ERROR);
+            });
+        if let Err(error) = toy_processor.apply_opening_balances(&balances) {
+            eprintln!("{}", error);
+            std::process::exit(exit::IO_ERROR);
+        }
+    }
+
+    if let Some(rules) = options.rules {
+        let loaded_rules: Vec<Rule> = load_rules(&rules).unwrap_or_else(|err| {
+            eprintln!("Error when trying to read rules: {}, {}", rules, err);
+            std::process::exit(exit::IO_ERROR);
+        });
+        toy_processor.set_rules(loaded_rules);
+    }
+
+    if let Some(policy) = options.policy {
+        let policies: Vec<LockPolicy> = load_policies(&policy).unwrap_or_else(|err| {
+            eprintln!("Error when trying to read policy: {}, {}", policy, err);
+            std::process::exit(exit::IO_ERROR);
+        });
+        toy_processor.set_policies(policies);
+    }
+
+    if let Some(length) = options.dispute_deadline {
+        let action: ExpiryAction = options
+            .on_dispute_expiry
+            .as_deref()
+            .unwrap_or("resolve")
+            .parse()
+            .unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(exit::USAGE_ERROR);
+            });
+        let unit: DeadlineUnit =
+            deadline_unit(options.dispute_deadline_unit.as_deref(), "dispute deadline");
+        toy_processor.set_dispute_deadlines(Deadlines::new(length, unit), action);
+    }
+
+    if let Some(length) = options.authorization_expiry {
+        let unit: DeadlineUnit = deadline_unit(
+            options.authorization_expiry_unit.as_deref(),
+            "authorization expiry",
+        );
+        toy_processor.set_authorization_deadlines(Deadlines::new(length, unit));
+    }
+
+    if let Some(window) = options.reorder_window {
+        toy_processor.set_reorder_window(window);
+    }
+
+    // Fingerprints of earlier runs, the file is created after the first run
+    if let Some(dedup_state) = options.dedup_state.as_deref() {
+        if std::path::Path::new(dedup_state).exists() {
+            if let Err(err) = toy_processor.load_dedup_state(dedup_state) {
+                eprintln!(
+                    "Error when trying to read deduplication state: {}, {}",
+                    dedup_state, err
+                );
+                std::process::exit(exit::IO_ERROR);
+            }
+        }
+    }
+
+    // Only the changes and rejected rows are reported, other reports and
+    // state files are left as is
+    if options.dry_run {
+        let dry_run: DryRun = toy_processor.dry_run();
+        if let Some(reason) = dry_run.abort {
+            abort(reason);
+        }
+        let mut writer = open_output(options.output.as_deref(), options.output_format);
+        eprintln!("DRY RUN: {}", dry_run.summary);
+        if let Err(error) = write_changes(&mut writer, &dry_run.changes) {
+            eprintln!("{}", error);
+            std::process::exit(exit::IO_ERROR);
+        }
+        if let Some(rejects) = options.rejects {
+            if let Err(error) = dry_run.write_rejects(&rejects) {
+                eprintln!("{}", error);
+                std::process::exit(exit::IO_ERROR);
+            }
+        }
+        if dry_run.summary.rejected > 0 {
+            std::process::exit(exit::REJECTED_ROWS);
+        }
+        return;
+    }
+
+    if let Some(row) = options.as_of_row {
+        let mut replayer: Replayer =
+            Replayer::new(filename, toy_processor, DEFAULT_CHECKPOINT_INTERVAL);
+        toy_processor = replayer.state_at_row(row).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(exit::IO_ERROR);
+        });
+    } else if let Some(timestamp) = options.as_of_timestamp {
+        let mut replayer: Replayer =
+            Replayer::new(filename, toy_processor, DEFAULT_CHECKPOINT_INTERVAL);
+        toy_processor = replayer
+            .state_at_timestamp(timestamp)
+            .unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(exit::IO_ERROR);
+            });
+    } else {
+        toy_processor.process_transactions();
+    }
 
-    if let Err(error) = toy_processor.print_clients() {
+    if options.summary {
+        let file_summaries: &[(String, RunSummary)] = toy_processor.get_file_summaries();
+        if file_summaries.len() > 1 {
+            for (filename, summary) in file_summaries {
+                eprintln!("SUMMARY: {}: {}", filename, summary);
+            }
+        }
+        eprintln!("SUMMARY: {}", toy_processor.get_summary());
+    }
+
+    // Nothing is written when the error budget is exceeded
+    if let Some(reason) = toy_processor.get_abort_reason() {
+        abort(reason);
+    }
+
+    let mut writer = open_output(options.output.as_deref(), options.output_format);
+    // Input files that couldn't be read to the end count as I/O errors too
+    let mut io_failed: bool = toy_processor.get_read_errors() > 0;
+
+    // A replay only shows past balances, like a dry run it records nothing
+    let replay: bool = options.as_of_row.is_some() || options.as_of_timestamp.is_some();
+    if let Some(dedup_state) = options.dedup_state.filter(|_| !replay) {
+        if let Err(error) = toy_processor.save_dedup_state(&dedup_state) {
+            eprintln!("{}", error);
+            io_failed = true;
+        }
+    }
+
+    let printed = if mode == Mode::Stats {
+        write_stats(&mut writer, &toy_processor)
+    } else {
+        toy_processor.write_clients(&mut writer, options.client_details)
+    };
+    if let Err(error) = printed {
         eprintln!("{}", error);
+        io_failed = true;
+    }
+
+    if let Some(rejects) = options.rejects {
+        if let Err(error) = toy_processor.write_rejects(&rejects) {
+            eprintln!("{}", error);
+            io_failed = true;
+        }
+    }
+
+    if let Some(overdraft_report) = options.overdraft_report {
+        if let Err(error) = toy_processor.write_overdraft_report(&overdraft_report) {
+            eprintln!("{}", error);
+            io_failed = true;
+        }
+    }
+
+    if let Some(risk_report) = options.risk_report {
+        if let Err(error) = toy_processor.write_risk_report(&risk_report) {
+            eprintln!("{}", error);
+            io_failed = true;
+        }
+    }
+
+    match options.synthetic_events {
+        Some(synthetic_events) => {
+            if let Err(error) = toy_processor.write_synthetic_events(&synthetic_events) {
+                eprintln!("{}", error);
+                io_failed = true;
+            }
+        }
+        None => {
+            for (row, transaction) in toy_processor.get_synthetic_events() {
+                let expired: &str = match transaction.get_tx_type() {
+                    TxType::Void => "AUTHORIZATION",
+                    _ => "DISPUTE",
+                };
+                eprintln!(
+                    "EXPIRED {}: Transaction {} of client {} got an automatic {} at row {}.",
+                    expired,
+                    transaction.get_tx_id(),
+                    transaction.get_client_id(),
+                    transaction.get_tx_type(),
+                    row
+                );
+            }
+        }
+    }
+
+    // Open disputes and authorizations whose deadline hasn't passed
+    for (pending, open) in toy_processor
+        .get_pending_disputes()
+        .into_iter()
+        .map(|pending| (pending, "DISPUTE"))
+        .chain(
+            toy_processor
+                .get_pending_authorizations()
+                .into_iter()
+                .map(|pending| (pending, "AUTHORIZATION")),
+        )
+    {
+        eprintln!(
+            "PENDING {}: Transaction {} of client {} is still open, its deadline passes at {} {}.",
+            open, pending.tx, pending.client, pending.unit, pending.deadline
+        );
+    }
+
+    match options.alerts {
+        Some(alerts) => {
+            if let Err(error) = toy_processor.write_alerts(&alerts) {
+                eprintln!("{}", error);
+                io_failed = true;
+            }
+        }
+        None => {
+            for alert in toy_processor.get_alerts() {
+                eprintln!(
+                    "ALERT: Transaction {} of client {} triggered rule {} ({}).",
+                    alert.tx, alert.client, alert.rule, alert.action
+                );
+            }
+        }
+    }
+
+    let mut verification_failed: bool = false;
+    if options.verify || mode == Mode::Verify {
+        let issues: Vec<String> = toy_processor.verify();
+        for issue in issues.iter() {
+            eprintln!("VERIFY ERROR: {}", issue);
+        }
+        if issues.is_empty() {
+            eprintln!("Verification passed.");
+        } else {
+            eprintln!("Verification failed with {} issue(s).", issues.len());
+            verification_failed = true;
+        }
+    }
+
+    // The most severe outcome decides the exit code
+    if io_failed {
+        std::process::exit(exit::IO_ERROR);
+    }
+    if verification_failed {
+        std::process::exit(exit::CHECK_FAILED);
+    }
+    if toy_processor.get_summary().rejected > 0 {
+        std::process::exit(exit::REJECTED_ROWS);
+    }
+}
+
+fn main() {
+    let cli: Cli = Cli::parse();
+
+    match cli.command {
+        None => process_command(cli.process, Mode::Process),
+        Some(Command::Process(options)) => process_command(options, Mode::Process),
+        Some(Command::Verify(options)) => process_command(options, Mode::Verify),
+        Some(Command::Stats(options)) => process_command(options, Mode::Stats),
+        Some(Command::Replay(options)) => process_command(options, Mode::Replay),
+        Some(Command::Diff(args)) => diff_command(args),
     }
 }
@@ -0,0 +1,140 @@
+use crate::client::Client;
+use crate::error::p_error;
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyKind {
+    // Chargebacks divided by deposits above `limit`
+    ChargebackRatio,
+    // Disputes divided by deposits above `limit`
+    DisputeRatio,
+    // At least `limit` disputes open at the same time
+    OpenDisputes,
+}
+
+impl fmt::Display for PolicyKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyKind::ChargebackRatio => write!(f, "chargeback_ratio"),
+            PolicyKind::DisputeRatio => write!(f, "dispute_ratio"),
+            PolicyKind::OpenDisputes => write!(f, "open_disputes"),
+        }
+    }
+}
+
+// One row of the policy file, e.g.
+//
+// policy,limit
+// chargeback_ratio,0.1
+// open_disputes,3
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct LockPolicy {
+    pub policy: PolicyKind,
+    pub limit: f32,
+}
+
+impl LockPolicy {
+    pub fn is_triggered(&self, client: &Client) -> bool {
+        match self.policy {
+            PolicyKind::ChargebackRatio => client.get_chargeback_ratio() > self.limit,
+            PolicyKind::DisputeRatio => client.get_dispute_ratio() > self.limit,
+            PolicyKind::OpenDisputes => client.get_open_dispute_count() as f32 >= self.limit,
+        }
+    }
+}
+
+pub fn load_policies(filename: &str) -> Result<Vec<LockPolicy>, Box<dyn Error>> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(filename)?;
+
+    let mut policies: Vec<LockPolicy> = Vec::new();
+    for row in csv_reader.deserialize() {
+        let policy: LockPolicy = row?;
+
+        if !policy.limit.is_finite() || policy.limit < 0_f32 {
+            return p_error(format!("Policy {} needs a positive limit.", policy.policy));
+        }
+
+        policies.push(policy);
+    }
+
+    Ok(policies)
+}
+
+// First policy that requires the client account to be locked
+pub fn evaluate(policies: &[LockPolicy], client: &Client) -> Option<PolicyKind> {
+    policies
+        .iter()
+        .find(|policy| policy.is_triggered(client))
+        .map(|policy| policy.policy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::{cid, tid};
+    use crate::transaction::{Transaction, TxType};
+
+    #[test]
+    fn test_evaluate_policies() {
+        let policies: Vec<LockPolicy> = vec![
+            LockPolicy {
+                policy: PolicyKind::OpenDisputes,
+                limit: 2_f32,
+            },
+            LockPolicy {
+                policy: PolicyKind::ChargebackRatio,
+                limit: 0.25_f32,
+            },
+        ];
+        let mut client: Client = Client::new(cid(1));
+        for tx in 1..=3 {
+            let deposit: Transaction = Transaction {
+                tx_type: TxType::Deposit,
+                client: cid(1),
+                tx: tid(tx),
+                amount: Some(1_f32),
+                timestamp: None,
+            };
+            assert_eq!((), client.consume_deposit(deposit).unwrap());
+        }
+        assert_eq!(None, evaluate(&policies, &client));
+
+        let mut dispute: Transaction = Transaction {
+            tx_type: TxType::Dispute,
+            client: cid(1),
+            tx: tid(1),
+            amount: None,
+            timestamp: None,
+        };
+        assert_eq!((), client.consume_dispute(dispute).unwrap());
+        assert_eq!(None, evaluate(&policies, &client));
+
+        // One chargeback out of three deposits
+        let chargeback: Transaction = Transaction {
+            tx_type: TxType::Chargeback,
+            client: cid(1),
+            tx: tid(1),
+            amount: None,
+            timestamp: None,
+        };
+        assert_eq!((), client.consume_chargeback(chargeback).unwrap());
+        assert_eq!(
+            Some(PolicyKind::ChargebackRatio),
+            evaluate(&policies, &client)
+        );
+
+        // Open disputes are checked first
+        dispute.tx = tid(2);
+        assert_eq!((), client.consume_dispute(dispute).unwrap());
+        dispute.tx = tid(3);
+        assert_eq!((), client.consume_dispute(dispute).unwrap());
+        assert_eq!(Some(PolicyKind::OpenDisputes), evaluate(&policies, &client));
+        assert_eq!(3, client.get_dispute_count());
+        assert_eq!(1_f32, client.get_dispute_ratio());
+    }
+}
@@ -1,23 +1,233 @@
 use std::collections::HashMap;
 use std::error::Error;
+use std::io::{Read, Write};
 
-use crate::client::Client;
-use crate::transaction::{Transaction, TxType};
+use crate::batch::{Atomicity, Batch, Snapshot};
+use crate::budget::{AbortReason, ErrorBudget};
+use crate::client::{Client, ClientStatus};
+use crate::columns::{ColumnAliases, ColumnMap};
+use crate::compression::open_input;
+use crate::config::{ClientConfig, OpeningBalance};
+use crate::deadlines::{Deadlines, PendingDeadline};
+use crate::dedup::{file_fingerprint, row_key, Deduplicator};
+use crate::diff::{diff_states, print_changes, ClientChange, ClientState, Tolerance};
+use crate::dispute::ExpiryAction;
+use crate::error::p_error;
+use crate::format::Format;
+use crate::ids::{ClientId, TxId};
+use crate::ledger::Ledger;
+use crate::location::RowLocation;
+use crate::policy::{evaluate, LockPolicy};
+use crate::reorder::ReorderBuffer;
+use crate::rules::{Alert, Rule, RulesEngine};
+use crate::summary::RunSummary;
+use crate::transaction::{Transaction, TxType, UnexpectedAmount};
 
+#[derive(Clone)]
 pub struct Processor {
-    filename: String,
-    clients: HashMap<u16, Client>,
+    // Input files, processed in order as one stream
+    filenames: Vec<String>,
+    clients: HashMap<ClientId, Client>,
+    // Reject transactions of clients missing from the client registry
+    strict_clients: bool,
+    // Number of transactions given to process_transaction so far
+    rows_processed: u64,
+    // Latest event time seen so far
+    latest_timestamp: Option<u64>,
+    // Reorders input rows to event-time order before processing them
+    reorder_buffer: Option<ReorderBuffer>,
+    rules: RulesEngine,
+    // Thresholds that lock client accounts automatically
+    policies: Vec<LockPolicy>,
+    // Open disputes are resolved or charged back once their deadline passes
+    dispute_deadlines: Option<Deadlines>,
+    dispute_expiry_action: ExpiryAction,
+    // Open authorizations are voided once they expire
+    authorization_deadlines: Option<Deadlines>,
+    // Resolve/chargeback/void transactions generated by expired disputes
+    // and authorizations, with the row they were generated at
+    synthetic_events: Vec<(u64, Transaction)>,
+    // Skips rows and files that were already ingested
+    deduplicator: Deduplicator,
+    summary: RunSummary,
+    // Counts of every input file
+    file_summaries: Vec<(String, RunSummary)>,
+    input_format: Format,
+    // Header names of the input columns
+    column_aliases: ColumnAliases,
+    // Input files have no header, columns are read by position
+    headerless: bool,
+    unexpected_amount: UnexpectedAmount,
+    error_budget: ErrorBudget,
+    atomicity: Atomicity,
+    // Undo log of the group of rows being applied
+    batch: Option<Batch>,
+    rolled_back_batches: u64,
+    // Input files that couldn't be read to the end
+    read_errors: u64,
+    // Set once the error budget is exceeded, no more rows are processed
+    abort: Option<AbortReason>,
+    // Location of the rows waiting in the reorder buffer
+    buffered_locations: HashMap<(TxType, ClientId, TxId), RowLocation>,
+    rejects: Vec<Reject>,
 }
 
 // Declare const headers with lazy_static so allocation is possible at
 // runtime https://docs.rs/lazy_static/latest/lazy_static/
 lazy_static! {
-    static ref FULL_HEADER: csv::ByteRecord =
-        csv::ByteRecord::from(vec!["type", "client", "tx", "amount"]);
-    static ref PARTIAL_HEADER: csv::ByteRecord =
-        csv::ByteRecord::from(vec!["type", "client", "tx"]);
     static ref CSV_TOP_HEADER: csv::ByteRecord =
         csv::ByteRecord::from(vec!["client", "available", "held", "total", "locked"]);
+    static ref CSV_DETAILED_HEADER: csv::ByteRecord = csv::ByteRecord::from(vec![
+        "client",
+        "available",
+        "held",
+        "total",
+        "locked",
+        "name",
+        "status"
+    ]);
+    static ref REJECTS_HEADER: csv::ByteRecord = csv::ByteRecord::from(vec![
+        "type",
+        "client",
+        "tx",
+        "amount",
+        "timestamp",
+        "error",
+        "file",
+        "line",
+        "byte",
+        "row"
+    ]);
+    static ref ALERTS_HEADER: csv::ByteRecord =
+        csv::ByteRecord::from(vec!["row", "timestamp", "client", "tx", "rule", "action"]);
+    static ref RISK_HEADER: csv::ByteRecord = csv::ByteRecord::from(vec![
+        "client",
+        "deposits",
+        "disputes",
+        "chargebacks",
+        "open_disputes",
+        "dispute_ratio",
+        "chargeback_ratio",
+        "locked",
+        "lock_reason"
+    ]);
+    static ref SYNTHETIC_EVENTS_HEADER: csv::ByteRecord =
+        csv::ByteRecord::from(vec!["row", "type", "client", "tx", "timestamp"]);
+    static ref OVERDRAFT_HEADER: csv::ByteRecord = csv::ByteRecord::from(vec![
+        "client",
+        "available",
+        "credit_limit",
+        "overdraft",
+        "peak_overdraft"
+    ]);
+}
+
+// Risk counters change with every transaction, so lock policies are
+// checked again after each one
+fn apply_policies(policies: &[LockPolicy], client: &mut Client) {
+    if !client.is_locked() {
+        if let Some(policy) = evaluate(policies, client) {
+            client.lock_account_for_policy(policy);
+        }
+    }
+}
+
+// Create Builder from file
+// - allow different length rows
+// - read the first row as header, unless the input is headerless
+// Fields keep their spaces so rejected rows can be reported as they were
+// read, the column map trims them.
+pub fn reader_builder(format: Format, headerless: bool) -> csv::ReaderBuilder {
+    let mut builder = csv::ReaderBuilder::new();
+    builder
+        .delimiter(format.get_delimiter())
+        .flexible(true)
+        .has_headers(!headerless);
+
+    builder
+}
+
+// Gzip and zstd compressed files are decompressed while they're read
+pub fn open_reader(
+    filename: &str,
+    format: Format,
+    headerless: bool,
+) -> Result<csv::Reader<Box<dyn Read>>, Box<dyn Error>> {
+    Ok(reader_builder(format, headerless).from_reader(open_input(filename)?))
+}
+
+// Input row that was rejected, with the reason. Rows that couldn't be
+// parsed have no transaction, events generated by the processor have no
+// location.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reject {
+    pub transaction: Option<Transaction>,
+    pub error: String,
+    pub location: Option<RowLocation>,
+}
+
+impl Reject {
+    pub fn record(&self) -> csv::ByteRecord {
+        let fields: Vec<String> = match self.transaction {
+            Some(transaction) => vec![
+                transaction.get_tx_type().to_string(),
+                format!("{}", transaction.get_client_id()),
+                format!("{}", transaction.get_tx_id()),
+                format!("{:.4}", transaction.get_amount()),
+                transaction
+                    .get_timestamp()
+                    .map(|timestamp| timestamp.to_string())
+                    .unwrap_or_default(),
+            ],
+            None => vec![String::new(); 5],
+        };
+
+        let mut record = csv::ByteRecord::from(fields);
+        record.push_field(self.error.as_bytes());
+        match &self.location {
+            Some(location) => {
+                record.push_field(location.file.as_bytes());
+                record.push_field(location.line.to_string().as_bytes());
+                record.push_field(location.byte.to_string().as_bytes());
+                record.push_field(location.row.as_bytes());
+            }
+            None => (0..4).for_each(|_| record.push_field(b"")),
+        }
+
+        record
+    }
+}
+
+// Outcome of processing the input without keeping the result
+#[derive(Debug, Clone, PartialEq)]
+pub struct DryRun {
+    pub changes: Vec<ClientChange>,
+    pub rejects: Vec<Reject>,
+    pub summary: RunSummary,
+    // Set when the error budget would abort the run
+    pub abort: Option<AbortReason>,
+}
+
+impl DryRun {
+    pub fn print_changes(&self) -> Result<(), Box<dyn Error>> {
+        print_changes(&self.changes)
+    }
+
+    pub fn write_rejects(&self, filename: &str) -> Result<(), Box<dyn Error>> {
+        write_rejects(filename, &self.rejects)
+    }
+}
+
+fn write_rejects(filename: &str, rejects: &[Reject]) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(filename)?;
+
+    writer.write_byte_record(&REJECTS_HEADER)?;
+
+    for reject in rejects {
+        writer.write_byte_record(&reject.record())?;
+    }
+
+    Ok(())
 }
 
 /*******************************
@@ -29,61 +239,626 @@ Processes received CSV files into Client accounts hashmap.
 impl Processor {
     pub fn new(filename: String) -> Self {
         Self {
-            filename: filename,
+            filenames: vec![filename],
             clients: HashMap::new(),
+            strict_clients: false,
+            rows_processed: 0,
+            latest_timestamp: None,
+            reorder_buffer: None,
+            rules: RulesEngine::default(),
+            policies: Vec::new(),
+            dispute_deadlines: None,
+            dispute_expiry_action: ExpiryAction::default(),
+            authorization_deadlines: None,
+            synthetic_events: Vec::new(),
+            deduplicator: Deduplicator::new(),
+            summary: RunSummary::default(),
+            file_summaries: Vec::new(),
+            input_format: Format::default(),
+            column_aliases: ColumnAliases::default(),
+            headerless: false,
+            unexpected_amount: UnexpectedAmount::default(),
+            error_budget: ErrorBudget::default(),
+            atomicity: Atomicity::default(),
+            batch: None,
+            rolled_back_batches: 0,
+            read_errors: 0,
+            abort: None,
+            buffered_locations: HashMap::new(),
+            rejects: Vec::new(),
+        }
+    }
+
+    pub fn set_rules(&mut self, rules: Vec<Rule>) {
+        self.rules = RulesEngine::new(rules);
+    }
+
+    pub fn set_policies(&mut self, policies: Vec<LockPolicy>) {
+        self.policies = policies;
+    }
+
+    // Rows are reordered to event-time order within a window of the
+    // given number of rows
+    pub fn set_reorder_window(&mut self, window: usize) {
+        self.reorder_buffer = Some(ReorderBuffer::new(window));
+    }
+
+    pub fn set_dispute_deadlines(&mut self, dispute_deadlines: Deadlines, action: ExpiryAction) {
+        self.dispute_deadlines = Some(dispute_deadlines);
+        self.dispute_expiry_action = action;
+    }
+
+    pub fn set_authorization_deadlines(&mut self, authorization_deadlines: Deadlines) {
+        self.authorization_deadlines = Some(authorization_deadlines);
+    }
+
+    pub fn get_synthetic_events(&self) -> &[(u64, Transaction)] {
+        &self.synthetic_events
+    }
+
+    // Disputes still open whose deadline hasn't passed
+    pub fn get_pending_disputes(&self) -> Vec<PendingDeadline> {
+        let deadlines: &Deadlines = match self.dispute_deadlines.as_ref() {
+            Some(deadlines) => deadlines,
+            None => return Vec::new(),
+        };
+
+        deadlines
+            .pending()
+            .filter(|pending| {
+                self.clients
+                    .get(&pending.client)
+                    .is_some_and(|client| client.check_disputed_transaction(pending.tx))
+            })
+            .collect()
+    }
+
+    // Authorizations still open whose expiry hasn't passed
+    pub fn get_pending_authorizations(&self) -> Vec<PendingDeadline> {
+        let deadlines: &Deadlines = match self.authorization_deadlines.as_ref() {
+            Some(deadlines) => deadlines,
+            None => return Vec::new(),
+        };
+
+        deadlines
+            .pending()
+            .filter(|pending| {
+                self.clients
+                    .get(&pending.client)
+                    .is_some_and(|client| client.check_open_authorization(pending.tx))
+            })
+            .collect()
+    }
+
+    pub fn set_filenames(&mut self, filenames: Vec<String>) {
+        self.filenames = filenames;
+    }
+
+    pub fn get_file_summaries(&self) -> &[(String, RunSummary)] {
+        &self.file_summaries
+    }
+
+    pub fn set_input_format(&mut self, input_format: Format) {
+        self.input_format = input_format;
+    }
+
+    pub fn get_input_format(&self) -> Format {
+        self.input_format
+    }
+
+    pub fn set_column_aliases(&mut self, column_aliases: ColumnAliases) {
+        self.column_aliases = column_aliases;
+    }
+
+    pub fn set_headerless(&mut self, headerless: bool) {
+        self.headerless = headerless;
+    }
+
+    pub fn set_unexpected_amount(&mut self, unexpected_amount: UnexpectedAmount) {
+        self.unexpected_amount = unexpected_amount;
+    }
+
+    pub fn set_error_budget(&mut self, error_budget: ErrorBudget) {
+        self.error_budget = error_budget;
+    }
+
+    pub fn set_atomicity(&mut self, atomicity: Atomicity) {
+        self.atomicity = atomicity;
+    }
+
+    pub fn get_rolled_back_batches(&self) -> u64 {
+        self.rolled_back_batches
+    }
+
+    pub fn get_read_errors(&self) -> u64 {
+        self.read_errors
+    }
+
+    pub fn get_abort_reason(&self) -> Option<AbortReason> {
+        self.abort
+    }
+
+    pub fn is_headerless(&self) -> bool {
+        self.headerless
+    }
+
+    // Columns of an input file, read from its header unless the input is
+    // headerless
+    pub fn column_map<R: Read>(
+        &self,
+        csv_reader: &mut csv::Reader<R>,
+    ) -> Result<ColumnMap, Box<dyn Error>> {
+        let mut columns: ColumnMap = if self.headerless {
+            ColumnMap::positional()
+        } else {
+            ColumnMap::from_header(csv_reader.byte_headers()?, &self.column_aliases)?
+        };
+        columns.set_unexpected_amount(self.unexpected_amount);
+
+        Ok(columns)
+    }
+
+    pub fn get_rejects(&self) -> &[Reject] {
+        &self.rejects
+    }
+
+    pub fn get_summary(&self) -> RunSummary {
+        self.summary
+    }
+
+    pub fn load_dedup_state(&mut self, filename: &str) -> Result<(), Box<dyn Error>> {
+        self.deduplicator.load(filename)
+    }
+
+    pub fn save_dedup_state(&self, filename: &str) -> Result<(), Box<dyn Error>> {
+        self.deduplicator.save(filename)
+    }
+
+    // Balances of every client, ordered by client id
+    pub fn get_client_states(&self) -> Vec<ClientState> {
+        let mut states: Vec<ClientState> = self.clients.values().map(ClientState::from).collect();
+        states.sort_by_key(|state| state.client);
+
+        states
+    }
+
+    pub fn get_client(&self, client_id: ClientId) -> Option<&Client> {
+        self.clients.get(&client_id)
+    }
+
+    pub fn get_alerts(&self) -> &[Alert] {
+        self.rules.get_alerts()
+    }
+
+    pub fn set_strict_clients(&mut self, strict_clients: bool) {
+        self.strict_clients = strict_clients;
+    }
+
+    // Registers the clients given in the client registry, so they are
+    // known before any transaction is processed
+    pub fn apply_client_configs(&mut self, configs: &[ClientConfig]) -> Result<(), Box<dyn Error>> {
+        for config in configs {
+            let client: &mut Client = self
+                .clients
+                .entry(config.client)
+                .or_insert_with(|| Client::new(config.client));
+
+            client.set_name(config.name.clone());
+            client.set_status(config.get_status());
+            client.set_credit_limit(config.get_credit_limit());
+            if config.get_initial_balance() != 0_f32 {
+                client.open_account(config.get_initial_balance(), 0_f32, false)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Seeds client accounts with balances carried over from another run or
+    // from a legacy system
+    pub fn apply_opening_balances(
+        &mut self,
+        opening_balances: &[OpeningBalance],
+    ) -> Result<(), Box<dyn Error>> {
+        for opening_balance in opening_balances {
+            if self.strict_clients && !self.clients.contains_key(&opening_balance.client) {
+                return p_error(format!(
+                    "Client {} of the opening balances is not registered.",
+                    opening_balance.client
+                ));
+            }
+
+            // Opening balances win over the initial balance of the registry
+            self.clients
+                .entry(opening_balance.client)
+                .or_insert_with(|| Client::new(opening_balance.client))
+                .reopen_account(
+                    opening_balance.available,
+                    opening_balance.held,
+                    opening_balance.locked,
+                )?;
         }
+
+        Ok(())
     }
+
     pub fn process_transactions(&mut self) {
-        // Create Builder from file
-        // - remove spaces
-        // - allow different length rows
-        let mut csv_reader = csv::ReaderBuilder::new()
-            .flexible(true)
-            .trim(csv::Trim::All)
-            .from_path(&self.filename)
-            .unwrap_or_else(|err| {
+        for filename in self.filenames.clone() {
+            let before: RunSummary = self.summary;
+            self.process_file(&filename);
+            self.file_summaries
+                .push((filename, self.summary.since(before)));
+            if self.abort.is_some() {
+                return;
+            }
+        }
+
+        self.flush();
+        self.check_error_rate();
+    }
+
+    // Reads one input file. Rows still waiting to be reordered are kept for
+    // the next file.
+    fn process_file(&mut self, filename: &str) {
+        let mut csv_reader = match open_reader(filename, self.input_format, self.headerless) {
+            Ok(csv_reader) => csv_reader,
+            Err(error) => {
                 eprintln!(
                     "Error when trying to read from CSV: {}, {}",
-                    self.filename, err
+                    filename, error
                 );
-                std::process::exit(1);
-            });
-
-        // Deserialize each row, based on headers length
-        for row in csv_reader.byte_records() {
-            if let Ok(result) = row {
-                let tx: Result<Transaction, csv::Error> = match result.len() {
-                    4 => result.deserialize(Some(&FULL_HEADER)),
-                    3 => result.deserialize(Some(&PARTIAL_HEADER)),
-                    _ => {
-                        eprintln!("Only rows with 3 or 4 fields are allowed.");
-                        continue;
-                    }
-                };
-                if let Err(error) = tx {
-                    eprintln!("Deserialization error: {}.", error);
+                self.read_errors += 1;
+                return;
+            }
+        };
+
+        // A redelivered file is skipped as a whole
+        let fingerprint: Option<u64> = match file_fingerprint(filename) {
+            Ok(fingerprint) if self.deduplicator.check_file(fingerprint) => {
+                self.summary.duplicate_files += 1;
+                return;
+            }
+            Ok(fingerprint) => Some(fingerprint),
+            Err(error) => {
+                eprintln!("{}", error);
+                None
+            }
+        };
+
+        let columns: ColumnMap = match self.column_map(&mut csv_reader) {
+            Ok(columns) => columns,
+            Err(error) => {
+                eprintln!(
+                    "Error when trying to read from CSV: {}, {}",
+                    filename, error
+                );
+                // Like a broken row, a broken stream ends the file. A header
+                // without the mandatory columns stops the run.
+                match error.downcast_ref::<csv::Error>() {
+                    Some(error) if error.is_io_error() => self.read_errors += 1,
+                    _ => self.abort = Some(AbortReason::InvalidHeader),
+                }
+                return;
+            }
+        };
+
+        if self.atomicity == Atomicity::File {
+            self.begin_batch();
+        }
+        // Batch id of the open group, when grouping by batch
+        let mut current_batch: Option<String> = None;
+        let mut rolled_back: bool = false;
+
+        for result in csv_reader.byte_records() {
+            let record: csv::ByteRecord = match result {
+                Ok(record) => record,
+                // A broken compressed stream can't be read any further
+                Err(error) if error.is_io_error() => {
+                    eprintln!(
+                        "Error when trying to read from CSV: {}, {}",
+                        filename, error
+                    );
+                    self.read_errors += 1;
+                    break;
+                }
+                // Malformed rows are rejected, never skipped silently
+                Err(error) => {
+                    let location: RowLocation = RowLocation::from_error(filename, &error);
+                    self.reject_row(error.into(), Some(location));
                     continue;
                 }
+            };
 
-                if let Err(error) = self.process_transaction(tx.unwrap()) {
-                    eprintln!("{}", error);
+            if self.atomicity == Atomicity::Batch {
+                let batch: Option<String> = columns.get_batch(&record);
+                if batch != current_batch {
+                    if let Some(batch_id) = current_batch.take() {
+                        rolled_back |=
+                            self.end_batch(&format!("batch {} of {}", batch_id, filename));
+                    }
+                    if batch.is_some() {
+                        self.begin_batch();
+                    }
+                    current_batch = batch;
                 }
             }
+
+            let location: RowLocation =
+                RowLocation::new(filename, &record, self.input_format.get_delimiter());
+            match columns.parse(&record) {
+                Ok(transaction) => self.submit_transaction(transaction, Some(location)),
+                Err(error) => self.reject_row(error, Some(location)),
+            }
+            if self.abort.is_some() {
+                break;
+            }
+        }
+
+        rolled_back |= match current_batch {
+            Some(batch_id) => self.end_batch(&format!("batch {} of {}", batch_id, filename)),
+            None => self.end_batch(filename),
+        };
+
+        // A file with a rolled back group can be sent again
+        if let Some(fingerprint) = fingerprint.filter(|_| rolled_back) {
+            self.deduplicator.forget_file(fingerprint);
+        }
+    }
+
+    // Starts a group of rows that is applied all-or-nothing
+    fn begin_batch(&mut self) {
+        let snapshot: Snapshot = Snapshot {
+            rows_processed: self.rows_processed,
+            latest_timestamp: self.latest_timestamp,
+            synthetic_events: self.synthetic_events.len(),
+            alerts: self.rules.get_alerts().len(),
+        };
+        for deadlines in [
+            self.dispute_deadlines.as_mut(),
+            self.authorization_deadlines.as_mut(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            deadlines.begin_group();
+        }
+        self.batch = Some(Batch::new(
+            self.summary.rejected,
+            self.read_errors,
+            snapshot,
+        ));
+    }
+
+    // Keeps the changes of the open group, or rolls all of them back when
+    // one of its rows was rejected or the input broke off. Returns true
+    // when the group was rolled back.
+    fn end_batch(&mut self, label: &str) -> bool {
+        let batch: Batch = match self.batch.take() {
+            Some(batch) => batch,
+            None => return false,
+        };
+        let rejected: u64 = self.summary.rejected - batch.rejected;
+        let rolled_back: bool = rejected > 0 || self.read_errors != batch.read_errors;
+        for deadlines in [
+            self.dispute_deadlines.as_mut(),
+            self.authorization_deadlines.as_mut(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if rolled_back {
+                deadlines.rollback_group();
+            } else {
+                deadlines.commit_group();
+            }
+        }
+        if !rolled_back {
+            return false;
+        }
+
+        // Rolled back rows can be sent again
+        for transaction in batch.rows.iter() {
+            self.deduplicator.forget_row(*transaction);
+        }
+
+        let rows: usize = batch.rows.len();
+        let snapshot: Snapshot = batch.rollback(&mut self.clients, &mut self.rules);
+        self.rows_processed = snapshot.rows_processed;
+        self.latest_timestamp = snapshot.latest_timestamp;
+        self.synthetic_events.truncate(snapshot.synthetic_events);
+        self.rolled_back_batches += 1;
+
+        eprintln!(
+            "ROLLED BACK: {}: {} row(s) undone after {} rejected row(s).",
+            label, rows, rejected
+        );
+
+        true
+    }
+
+    // Processes the input file on a copy of the current state and returns
+    // the clients it would change, leaving this processor untouched
+    pub fn dry_run(&self) -> DryRun {
+        let mut preview: Processor = self.clone();
+        preview.summary = RunSummary::default();
+        preview.rejects = Vec::new();
+        preview.process_transactions();
+
+        DryRun {
+            changes: diff_states(
+                &self.get_client_states(),
+                &preview.get_client_states(),
+                &Tolerance::default(),
+            ),
+            rejects: preview.rejects,
+            summary: preview.summary,
+            abort: preview.abort,
+        }
+    }
+
+    // Processes a transaction read from the input, going through the
+    // reorder buffer when event-time ordering is enabled
+    pub fn submit_transaction(&mut self, transaction: Transaction, location: Option<RowLocation>) {
+        self.summary.rows += 1;
+        match self.deduplicator.check_row(transaction) {
+            Ok(true) => {
+                self.summary.duplicate_rows += 1;
+                return;
+            }
+            Ok(false) => {
+                if let Some(batch) = self.batch.as_mut() {
+                    batch.rows.push(transaction);
+                }
+            }
+            Err(error) => {
+                self.reject(Some(transaction), error, location);
+                return;
+            }
+        }
+
+        let (ready, location): (Option<Transaction>, Option<RowLocation>) =
+            match self.reorder_buffer.as_mut() {
+                Some(reorder_buffer) => match reorder_buffer.push(transaction) {
+                    Ok(ready) => {
+                        self.deduplicator.hold_row(transaction);
+                        // The row waits in the buffer with its location, and
+                        // the released one takes its own location back
+                        if let Some(location) = location {
+                            self.buffered_locations
+                                .insert(row_key(transaction), location);
+                        }
+                        let location: Option<RowLocation> =
+                            ready.and_then(|ready| self.buffered_locations.remove(&row_key(ready)));
+                        (ready, location)
+                    }
+                    Err(error) => {
+                        self.reject(Some(transaction), error, location);
+                        return;
+                    }
+                },
+                None => (Some(transaction), location),
+            };
+
+        if let Some(transaction) = ready {
+            self.apply_row(transaction, location);
+        }
+    }
+
+    // Applies an input row that passed deduplication. Only accepted rows
+    // are remembered, a rejected one can be sent again.
+    fn apply_row(&mut self, transaction: Transaction, location: Option<RowLocation>) {
+        match self.process_transaction(transaction) {
+            Ok(()) => self.deduplicator.record_row(transaction),
+            Err(error) => {
+                self.deduplicator.forget_row(transaction);
+                self.reject(Some(transaction), error, location);
+            }
+        }
+    }
+
+    // Counts an input row that couldn't be parsed
+    pub fn reject_row(&mut self, error: Box<dyn Error>, location: Option<RowLocation>) {
+        self.summary.rows += 1;
+        self.reject(None, error, location);
+    }
+
+    fn reject(
+        &mut self,
+        transaction: Option<Transaction>,
+        error: Box<dyn Error>,
+        location: Option<RowLocation>,
+    ) {
+        match &location {
+            Some(location) => eprintln!("{}: {} Row: {}", location, error, location.row),
+            None => eprintln!("{}", error),
+        }
+        self.summary.rejected += 1;
+        self.rejects.push(Reject {
+            transaction,
+            error: error.to_string(),
+            location,
+        });
+        if self.abort.is_none() {
+            self.abort = self.error_budget.check_errors(&self.summary);
+        }
+    }
+
+    // The error rate is only known once the whole input has been read
+    pub fn check_error_rate(&mut self) {
+        if self.abort.is_none() {
+            self.abort = self.error_budget.check_error_rate(&self.summary);
+        }
+    }
+
+    // Processes the rows still waiting to be reordered
+    pub fn flush(&mut self) {
+        let remaining: Vec<Transaction> = match self.reorder_buffer.as_mut() {
+            Some(reorder_buffer) => reorder_buffer.drain(),
+            None => Vec::new(),
+        };
+
+        for transaction in remaining {
+            let location: Option<RowLocation> =
+                self.buffered_locations.remove(&row_key(transaction));
+            self.apply_row(transaction, location);
         }
     }
 
     pub fn process_transaction(&mut self, transaction: Transaction) -> Result<(), Box<dyn Error>> {
+        self.rows_processed += 1;
+        if let Some(timestamp) = transaction.get_timestamp() {
+            self.latest_timestamp = self.latest_timestamp.max(Some(timestamp));
+        }
+
+        let result: Result<(), Box<dyn Error>> = self.apply_transaction(transaction);
+        self.expire_disputes();
+        self.expire_authorizations();
+
+        result
+    }
+
+    fn apply_transaction(&mut self, transaction: Transaction) -> Result<(), Box<dyn Error>> {
         // We retrieve the client
         // If he doesn't exist, we create a new one
-        let client_id: u16 = transaction.get_client_id();
+        let client_id: ClientId = transaction.get_client_id();
+        if let Some(batch) = self.batch.as_mut() {
+            batch.save_client(client_id, &self.clients, &self.rules);
+        }
 
         let client: &mut Client = if let Some(client) = self.clients.get_mut(&client_id) {
             client
+        } else if self.strict_clients {
+            return p_error(format!(
+                "Client {} of transaction {} is not registered.",
+                client_id,
+                transaction.get_tx_id()
+            ));
         } else {
             self.clients.insert(client_id, Client::new(client_id));
             self.clients.get_mut(&client_id).unwrap()
         };
 
+        match (client.get_status(), transaction.get_tx_type()) {
+            (ClientStatus::Closed, _) => {
+                return p_error(format!(
+                    "Client {} is closed and cannot accept transaction {}.",
+                    client_id,
+                    transaction.get_tx_id()
+                ));
+            }
+            (ClientStatus::Frozen, TxType::Withdrawal | TxType::Authorize) => {
+                return p_error(format!(
+                    "Client {} is frozen and cannot accept {} {}.",
+                    client_id,
+                    transaction.get_tx_type(),
+                    transaction.get_tx_id()
+                ));
+            }
+            _ => {}
+        }
+
+        self.rules.check(self.rows_processed, transaction, client)?;
+
         match transaction.get_tx_type() {
             TxType::Deposit => {
                 client.consume_deposit(transaction)?;
@@ -93,25 +868,448 @@ impl Processor {
                 client.consume_withdrawal(transaction)?;
             }
 
-            TxType::Dispute => client.consume_dispute(transaction)?,
+            TxType::Dispute => {
+                client.consume_dispute(transaction)?;
+                if let Some(deadlines) = self.dispute_deadlines.as_mut() {
+                    deadlines.add(
+                        self.rows_processed,
+                        transaction.get_timestamp(),
+                        client_id,
+                        transaction.get_tx_id(),
+                    );
+                }
+            }
 
             TxType::Resolve => client.consume_resolve(transaction)?,
 
             TxType::Chargeback => client.consume_chargeback(transaction)?,
+
+            TxType::Reversal => client.consume_reversal(transaction)?,
+
+            TxType::Authorize => {
+                client.consume_authorize(transaction)?;
+                if let Some(deadlines) = self.authorization_deadlines.as_mut() {
+                    deadlines.add(
+                        self.rows_processed,
+                        transaction.get_timestamp(),
+                        client_id,
+                        transaction.get_tx_id(),
+                    );
+                }
+            }
+
+            TxType::Capture => client.consume_capture(transaction)?,
+
+            TxType::Void => client.consume_void(transaction)?,
         }
 
+        self.rules.record(self.rows_processed, transaction);
+        apply_policies(&self.policies, client);
+
         Ok(())
     }
 
-    pub fn print_clients(&self) -> Result<(), Box<dyn Error>> {
-        let mut writer = csv::Writer::from_writer(std::io::stdout());
+    // Generates a resolve or chargeback for every dispute that reached its
+    // deadline and is still open
+    fn expire_disputes(&mut self) {
+        let deadlines: &mut Deadlines = match self.dispute_deadlines.as_mut() {
+            Some(deadlines) => deadlines,
+            None => return,
+        };
+        let expired: Vec<(ClientId, TxId)> =
+            deadlines.expire(self.rows_processed, self.latest_timestamp);
 
-        writer.write_byte_record(&CSV_TOP_HEADER)?;
+        for (client_id, tx_id) in expired {
+            if let Some(batch) = self.batch.as_mut() {
+                batch.save_client(client_id, &self.clients, &self.rules);
+            }
+            let client: &mut Client = match self.clients.get_mut(&client_id) {
+                Some(client) if client.check_disputed_transaction(tx_id) => client,
+                _ => continue,
+            };
+
+            let transaction: Transaction = Transaction {
+                tx_type: self.dispute_expiry_action.get_tx_type(),
+                client: client_id,
+                tx: tx_id,
+                amount: None,
+                timestamp: self.latest_timestamp,
+            };
+            let result: Result<(), Box<dyn Error>> = match transaction.get_tx_type() {
+                TxType::Chargeback => client.consume_chargeback(transaction),
+                _ => client.consume_resolve(transaction),
+            };
+
+            match result {
+                Ok(()) => {
+                    apply_policies(&self.policies, client);
+                    self.synthetic_events
+                        .push((self.rows_processed, transaction));
+                }
+                Err(error) => eprintln!("{}", error),
+            }
+        }
+    }
+
+    // Generates a void for every authorization that reached its deadline
+    // and wasn't captured or voided yet
+    fn expire_authorizations(&mut self) {
+        let deadlines: &mut Deadlines = match self.authorization_deadlines.as_mut() {
+            Some(deadlines) => deadlines,
+            None => return,
+        };
+        let expired: Vec<(ClientId, TxId)> =
+            deadlines.expire(self.rows_processed, self.latest_timestamp);
+
+        for (client_id, tx_id) in expired {
+            if let Some(batch) = self.batch.as_mut() {
+                batch.save_client(client_id, &self.clients, &self.rules);
+            }
+            let client: &mut Client = match self.clients.get_mut(&client_id) {
+                Some(client) if client.check_open_authorization(tx_id) => client,
+                _ => continue,
+            };
+
+            let transaction: Transaction = Transaction {
+                tx_type: TxType::Void,
+                client: client_id,
+                tx: tx_id,
+                amount: None,
+                timestamp: self.latest_timestamp,
+            };
+
+            match client.consume_void(transaction) {
+                Ok(()) => self
+                    .synthetic_events
+                    .push((self.rows_processed, transaction)),
+                Err(error) => eprintln!("{}", error),
+            }
+        }
+    }
+
+    // Combined balances of every client journal. Bank accounts are shared
+    // between clients, so their balances are summed up
+    pub fn trial_balance(&self) -> Ledger {
+        let mut ledger: Ledger = Ledger::new();
 
         for (_, client) in self.clients.iter() {
-            writer.write_byte_record(&client.record())?;
+            ledger.merge_balances(client.get_ledger());
         }
 
+        ledger
+    }
+
+    // Every client ledger has to agree with the client balances, and all
+    // postings, across all clients, have to sum up to zero
+    pub fn check_ledger(&self) -> Result<(), Box<dyn Error>> {
+        let mut client_ids: Vec<&ClientId> = self.clients.keys().collect();
+        client_ids.sort();
+
+        for client_id in client_ids {
+            let client: &Client = &self.clients[client_id];
+            client.get_ledger().check_invariant()?;
+            client.check_ledger()?;
+        }
+
+        self.trial_balance().check_invariant()
+    }
+
+    // Self-audit of every client account and of the combined ledger
+    pub fn verify(&self) -> Vec<String> {
+        let mut client_ids: Vec<&ClientId> = self.clients.keys().collect();
+        client_ids.sort();
+
+        let mut issues: Vec<String> = client_ids
+            .iter()
+            .flat_map(|client_id| self.clients[client_id].audit())
+            .collect();
+
+        if let Err(error) = self.check_ledger() {
+            issues.push(error.to_string());
+        }
+
+        issues
+    }
+
+    pub fn write_rejects(&self, filename: &str) -> Result<(), Box<dyn Error>> {
+        write_rejects(filename, self.get_rejects())
+    }
+
+    pub fn write_alerts(&self, filename: &str) -> Result<(), Box<dyn Error>> {
+        let mut writer = csv::Writer::from_path(filename)?;
+
+        writer.write_byte_record(&ALERTS_HEADER)?;
+
+        for alert in self.get_alerts() {
+            writer.write_byte_record(&alert.record())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_synthetic_events(&self, filename: &str) -> Result<(), Box<dyn Error>> {
+        let mut writer = csv::Writer::from_path(filename)?;
+
+        writer.write_byte_record(&SYNTHETIC_EVENTS_HEADER)?;
+
+        for (row, transaction) in self.synthetic_events.iter() {
+            writer.write_record(&[
+                row.to_string(),
+                transaction.get_tx_type().to_string(),
+                transaction.get_client_id().to_string(),
+                transaction.get_tx_id().to_string(),
+                transaction
+                    .get_timestamp()
+                    .map(|timestamp| timestamp.to_string())
+                    .unwrap_or_default(),
+            ])?;
+        }
+
+        Ok(())
+    }
+
+    // Dispute and chargeback counters of every client, with the reason
+    // of the account lock
+    pub fn write_risk_report(&self, filename: &str) -> Result<(), Box<dyn Error>> {
+        let mut writer = csv::Writer::from_path(filename)?;
+
+        writer.write_byte_record(&RISK_HEADER)?;
+
+        let mut clients: Vec<&Client> = self.clients.values().collect();
+        clients.sort_by_key(|client| client.get_id());
+
+        for client in clients {
+            writer.write_byte_record(&client.risk_record())?;
+        }
+
+        Ok(())
+    }
+
+    // Clients currently in overdraft, i.e. with negative available funds
+    pub fn write_overdraft_report(&self, filename: &str) -> Result<(), Box<dyn Error>> {
+        let mut writer = csv::Writer::from_path(filename)?;
+
+        writer.write_byte_record(&OVERDRAFT_HEADER)?;
+
+        let mut clients: Vec<&Client> = self
+            .clients
+            .values()
+            .filter(|client| client.get_available_amount() < 0_f32)
+            .collect();
+        clients.sort_by_key(|client| client.get_id());
+
+        for client in clients {
+            writer.write_byte_record(&client.overdraft_record())?;
+        }
+
+        Ok(())
+    }
+
+    // Writes the account list, with the name and status of every client
+    // when detailed
+    pub fn write_clients<W: Write>(
+        &self,
+        writer: &mut csv::Writer<W>,
+        detailed: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        if detailed {
+            writer.write_byte_record(&CSV_DETAILED_HEADER)?;
+        } else {
+            writer.write_byte_record(&CSV_TOP_HEADER)?;
+        }
+
+        for (_, client) in self.clients.iter() {
+            if detailed {
+                writer.write_byte_record(&client.detailed_record())?;
+            } else {
+                writer.write_byte_record(&client.record())?;
+            }
+        }
+
+        writer.flush()?;
+
         Ok(())
     }
+
+    // Same as print_clients, with the name and status of every client
+    pub fn print_client_details(&self) -> Result<(), Box<dyn Error>> {
+        self.write_clients(&mut csv::Writer::from_writer(std::io::stdout()), true)
+    }
+
+    pub fn print_clients(&self) -> Result<(), Box<dyn Error>> {
+        self.write_clients(&mut csv::Writer::from_writer(std::io::stdout()), false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::{cid, tid};
+    use crate::ledger::Account;
+
+    #[test]
+    fn test_processor_dry_run() {
+        let mut processor: Processor = Processor::new(format!(
+            "{}/tests/resources/disputes_example.csv",
+            env!("CARGO_MANIFEST_DIR")
+        ));
+        processor
+            .apply_opening_balances(&[OpeningBalance {
+                client: cid(4),
+                available: 1_f32,
+                held: 0_f32,
+                total: 1_f32,
+                locked: false,
+            }])
+            .unwrap();
+
+        let dry_run: DryRun = processor.dry_run();
+        assert_eq!(
+            vec![cid(1), cid(2), cid(3)],
+            dry_run
+                .changes
+                .iter()
+                .map(|change| change.client)
+                .collect::<Vec<ClientId>>()
+        );
+        assert!(dry_run.changes.iter().all(|change| change.before.is_none()));
+        assert_eq!(12, dry_run.summary.rows);
+        assert!(dry_run.rejects.is_empty());
+
+        // Nothing was kept
+        assert_eq!(1, processor.get_client_states().len());
+        assert_eq!(RunSummary::default(), processor.get_summary());
+
+        // Rejected rows are returned with the changes
+        let processor: Processor = Processor::new(format!(
+            "{}/tests/resources/authorizations_example.csv",
+            env!("CARGO_MANIFEST_DIR")
+        ));
+        let dry_run: DryRun = processor.dry_run();
+        assert_eq!(
+            vec![
+                "PROCESSOR ERROR: Authorization 5 is already captured/voided.",
+                "PROCESSOR ERROR: Invalid authorize transaction 7. Available amount is smaller than authorized amount.",
+                "PROCESSOR ERROR: Transaction with ID: 6 cannot have negative or 0 amount.",
+            ],
+            dry_run
+                .rejects
+                .iter()
+                .map(|reject| reject.error.as_str())
+                .collect::<Vec<&str>>()
+        );
+        assert!(processor.get_rejects().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_header_aborts() {
+        let processor: Processor = Processor::new(format!(
+            "{}/tests/resources/missing_columns.csv",
+            env!("CARGO_MANIFEST_DIR")
+        ));
+
+        // The preview stops at the header instead of ending the process
+        let dry_run: DryRun = processor.dry_run();
+        assert_eq!(Some(AbortReason::InvalidHeader), dry_run.abort);
+        assert_eq!(0, dry_run.summary.rows);
+    }
+
+    #[test]
+    fn test_reject_location_after_reordering() {
+        let resource: String = format!(
+            "{}/tests/resources/timestamps_example.csv",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut processor: Processor = Processor::new(resource.clone());
+        processor.set_reorder_window(2);
+
+        let mut reader = reader_builder(Format::Csv, false)
+            .from_path(&resource)
+            .unwrap();
+        let columns: ColumnMap = processor.column_map(&mut reader).unwrap();
+        let mut rows: Vec<(Transaction, RowLocation)> = reader
+            .byte_records()
+            .map(|record| {
+                let record: csv::ByteRecord = record.unwrap();
+                (
+                    columns.parse(&record).unwrap(),
+                    RowLocation::new(&resource, &record, b','),
+                )
+            })
+            .collect();
+        // A withdrawal nobody can afford, submitted first but released last
+        let mut overdraft: Transaction = rows[0].0;
+        overdraft.tx_type = TxType::Withdrawal;
+        overdraft.client = cid(9);
+        overdraft.timestamp = Some(u64::MAX);
+        let location: RowLocation = RowLocation {
+            line: 99,
+            ..rows[0].1.clone()
+        };
+        rows.insert(0, (overdraft, location.clone()));
+
+        for (transaction, location) in rows {
+            processor.submit_transaction(transaction, Some(location));
+        }
+        processor.flush();
+
+        let reject: &Reject = processor.get_rejects().last().unwrap();
+        assert_eq!(Some(overdraft), reject.transaction);
+        assert_eq!(Some(location), reject.location);
+    }
+
+    #[test]
+    fn test_processor_trial_balance() {
+        let mut processor: Processor = Processor::new(String::new());
+        let transactions: Vec<Transaction> = vec![
+            Transaction {
+                tx_type: TxType::Deposit,
+                client: cid(1),
+                tx: tid(1),
+                amount: Some(3_f32),
+                timestamp: None,
+            },
+            Transaction {
+                tx_type: TxType::Deposit,
+                client: cid(2),
+                tx: tid(2),
+                amount: Some(4_f32),
+                timestamp: None,
+            },
+            Transaction {
+                tx_type: TxType::Withdrawal,
+                client: cid(2),
+                tx: tid(3),
+                amount: Some(1_f32),
+                timestamp: None,
+            },
+            Transaction {
+                tx_type: TxType::Dispute,
+                client: cid(1),
+                tx: tid(1),
+                amount: None,
+                timestamp: None,
+            },
+        ];
+
+        for transaction in transactions {
+            assert_eq!((), processor.process_transaction(transaction).unwrap());
+        }
+
+        let trial_balance: Ledger = processor.trial_balance();
+        assert_eq!(
+            0,
+            trial_balance.get_balance(Account::ClientAvailable(cid(1)))
+        );
+        assert_eq!(
+            30000,
+            trial_balance.get_balance(Account::ClientHeld(cid(1)))
+        );
+        assert_eq!(
+            30000,
+            trial_balance.get_balance(Account::ClientAvailable(cid(2)))
+        );
+        assert_eq!(-60000, trial_balance.get_balance(Account::BankClearing));
+        assert_eq!((), processor.check_ledger().unwrap());
+    }
 }
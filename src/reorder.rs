@@ -0,0 +1,167 @@
+use crate::error::p_error;
+use crate::transaction::Transaction;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::error::Error;
+
+// Transaction waiting in the buffer, ordered by event time and then by
+// arrival, so rows with the same timestamp keep the file order
+#[derive(Debug, Clone, Copy)]
+struct BufferedTransaction {
+    timestamp: u64,
+    sequence: u64,
+    transaction: Transaction,
+}
+
+impl PartialEq for BufferedTransaction {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for BufferedTransaction {}
+
+impl PartialOrd for BufferedTransaction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BufferedTransaction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.timestamp, self.sequence).cmp(&(other.timestamp, other.sequence))
+    }
+}
+
+/*******************************
+< ReorderBuffer >
+
+Reorders transactions to event-time order within a bounded window of
+rows. Once a transaction leaves the buffer, its timestamp becomes the
+watermark, and rows with an older timestamp are rejected as late.
+
+*******************************/
+#[derive(Debug, Clone)]
+pub struct ReorderBuffer {
+    window: usize,
+    sequence: u64,
+    watermark: Option<u64>,
+    buffer: BinaryHeap<Reverse<BufferedTransaction>>,
+}
+
+impl ReorderBuffer {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            sequence: 0,
+            watermark: None,
+            buffer: BinaryHeap::new(),
+        }
+    }
+
+    // Adds a transaction to the buffer. When the buffer is full, the
+    // earliest transaction is released.
+    pub fn push(
+        &mut self,
+        transaction: Transaction,
+    ) -> Result<Option<Transaction>, Box<dyn Error>> {
+        let timestamp: u64 = match transaction.get_timestamp() {
+            Some(timestamp) => timestamp,
+            None => {
+                return p_error(format!(
+                    "Transaction {} has no timestamp and can't be reordered.",
+                    transaction.get_tx_id()
+                ))
+            }
+        };
+
+        if let Some(watermark) = self.watermark {
+            if timestamp < watermark {
+                return p_error(format!(
+                    "Transaction {} arrived too late. Timestamp {} is older than {}.",
+                    transaction.get_tx_id(),
+                    timestamp,
+                    watermark
+                ));
+            }
+        }
+
+        self.sequence += 1;
+        self.buffer.push(Reverse(BufferedTransaction {
+            timestamp,
+            sequence: self.sequence,
+            transaction,
+        }));
+
+        if self.buffer.len() > self.window {
+            return Ok(self.pop());
+        }
+
+        Ok(None)
+    }
+
+    // Releases every buffered transaction, in event-time order
+    pub fn drain(&mut self) -> Vec<Transaction> {
+        let mut transactions: Vec<Transaction> = Vec::new();
+
+        while let Some(transaction) = self.pop() {
+            transactions.push(transaction);
+        }
+
+        transactions
+    }
+
+    fn pop(&mut self) -> Option<Transaction> {
+        let Reverse(buffered) = self.buffer.pop()?;
+        self.watermark = Some(buffered.timestamp);
+
+        Some(buffered.transaction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::{cid, tid, TxId};
+    use crate::transaction::TxType;
+
+    fn deposit(tx: TxId, timestamp: Option<u64>) -> Transaction {
+        Transaction {
+            tx_type: TxType::Deposit,
+            client: cid(1),
+            tx,
+            amount: Some(1_f32),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_reorder_buffer() {
+        let mut buffer: ReorderBuffer = ReorderBuffer::new(2);
+
+        assert_eq!(None, buffer.push(deposit(tid(1), Some(30))).unwrap());
+        assert_eq!(None, buffer.push(deposit(tid(2), Some(10))).unwrap());
+        assert_eq!(
+            Some(deposit(tid(2), Some(10))),
+            buffer.push(deposit(tid(3), Some(20))).unwrap()
+        );
+
+        // Older than the last released transaction
+        assert_eq!(
+            "PROCESSOR ERROR: Transaction 4 arrived too late. Timestamp 5 is older than 10.",
+            buffer
+                .push(deposit(tid(4), Some(5)))
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "PROCESSOR ERROR: Transaction 5 has no timestamp and can't be reordered.",
+            buffer.push(deposit(tid(5), None)).unwrap_err().to_string()
+        );
+
+        assert_eq!(
+            vec![deposit(tid(3), Some(20)), deposit(tid(1), Some(30))],
+            buffer.drain()
+        );
+    }
+}
@@ -0,0 +1,323 @@
+use crate::columns::ColumnMap;
+use crate::compression::{detect_compression, Compression};
+use crate::error::p_error;
+use crate::format::Format;
+use crate::location::RowLocation;
+use crate::processor::{open_reader, reader_builder, Processor};
+use crate::transaction::Transaction;
+use std::error::Error;
+use std::io::Read;
+
+// Input rows between two checkpoints
+pub const DEFAULT_CHECKPOINT_INTERVAL: u64 = 10_000;
+// Checkpoints kept at most, each one holds a whole processor state
+pub const DEFAULT_MAX_CHECKPOINTS: usize = 16;
+
+// Processor state after a number of input rows, with the position of the
+// next row in the input file
+#[derive(Clone)]
+struct Checkpoint {
+    row: u64,
+    position: csv::Position,
+    // Latest timestamp seen up to this row
+    max_timestamp: Option<u64>,
+    processor: Processor,
+}
+
+/*******************************
+< Replayer >
+
+Reconstructs client balances as of an input row number or a timestamp by
+replaying the input file. Every `interval` rows the processor state is
+kept as a checkpoint, so later queries resume from the closest checkpoint
+instead of the beginning of the file. Once more than `max_checkpoints` are
+kept, every other one is dropped and the interval doubles, so memory stays
+bounded while the checkpoints still cover the whole file.
+
+*******************************/
+pub struct Replayer {
+    filename: String,
+    interval: u64,
+    max_checkpoints: usize,
+    // State before the first row, with all configuration applied
+    initial: Processor,
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl Replayer {
+    pub fn new(filename: String, initial: Processor, interval: u64) -> Self {
+        Self {
+            filename,
+            interval: interval.max(1),
+            max_checkpoints: DEFAULT_MAX_CHECKPOINTS,
+            initial,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    pub fn set_max_checkpoints(&mut self, max_checkpoints: usize) {
+        self.max_checkpoints = max_checkpoints.max(1);
+    }
+
+    // Number of checkpoints recorded so far
+    pub fn get_checkpoint_count(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    // State after the first `row` rows of the input
+    pub fn state_at_row(&mut self, row: u64) -> Result<Processor, Box<dyn Error>> {
+        self.replay(
+            |checkpoint| checkpoint.row <= row,
+            |current_row, _| current_row > row,
+        )
+    }
+
+    // State after every row with a timestamp up to `timestamp`. The input
+    // is expected in event-time order, replay stops at the first later row.
+    // Rows without a timestamp don't move the clock and are replayed.
+    pub fn state_at_timestamp(&mut self, timestamp: u64) -> Result<Processor, Box<dyn Error>> {
+        self.replay(
+            |checkpoint| checkpoint.max_timestamp.unwrap_or_default() <= timestamp,
+            |_, transaction| match transaction.and_then(|tx| tx.get_timestamp()) {
+                Some(row_timestamp) => row_timestamp > timestamp,
+                None => false,
+            },
+        )
+    }
+
+    fn replay<U, S>(&mut self, usable: U, stop: S) -> Result<Processor, Box<dyn Error>>
+    where
+        U: Fn(&Checkpoint) -> bool,
+        S: Fn(u64, Option<Transaction>) -> bool,
+    {
+        let format: Format = self.initial.get_input_format();
+        let headerless: bool = self.initial.is_headerless();
+        // Checkpoints are ordered by row, resume from the latest usable one
+        let start: Option<usize> = self.checkpoints.iter().rposition(&usable);
+
+        if detect_compression(&self.filename)? == Compression::None {
+            let mut csv_reader = reader_builder(format, headerless).from_path(&self.filename)?;
+            let columns: ColumnMap = self.initial.column_map(&mut csv_reader)?;
+            if let Some(index) = start {
+                csv_reader.seek(self.checkpoints[index].position.clone())?;
+            }
+            return self.resume(&mut csv_reader, &columns, start, stop);
+        }
+
+        // Compressed input can't be seeked, the rows before the checkpoint
+        // are read again without being processed
+        let mut csv_reader = open_reader(&self.filename, format, headerless)?;
+        let columns: ColumnMap = self.initial.column_map(&mut csv_reader)?;
+        if let Some(index) = start {
+            let mut record = csv::ByteRecord::new();
+            for _ in 0..self.checkpoints[index].row {
+                csv_reader.read_byte_record(&mut record)?;
+            }
+        }
+        self.resume(&mut csv_reader, &columns, start, stop)
+    }
+
+    // Processes the remaining rows, starting from the state of a checkpoint
+    fn resume<R: Read, S>(
+        &mut self,
+        csv_reader: &mut csv::Reader<R>,
+        columns: &ColumnMap,
+        start: Option<usize>,
+        stop: S,
+    ) -> Result<Processor, Box<dyn Error>>
+    where
+        S: Fn(u64, Option<Transaction>) -> bool,
+    {
+        let (mut row, mut max_timestamp, mut processor): (u64, Option<u64>, Processor) = match start
+        {
+            Some(index) => {
+                let checkpoint: &Checkpoint = &self.checkpoints[index];
+                (
+                    checkpoint.row,
+                    checkpoint.max_timestamp,
+                    checkpoint.processor.clone(),
+                )
+            }
+            None => (0, None, self.initial.clone()),
+        };
+
+        let delimiter: u8 = processor.get_input_format().get_delimiter();
+        let mut record = csv::ByteRecord::new();
+        loop {
+            let read: bool = match csv_reader.read_byte_record(&mut record) {
+                Ok(read) => read,
+                Err(error) => {
+                    return p_error(format!(
+                        "Replay stopped after row {} of {}. {}",
+                        row, self.filename, error
+                    ))
+                }
+            };
+            if !read {
+                break;
+            }
+
+            let parsed: Result<Transaction, Box<dyn Error>> = columns.parse(&record);
+            let location: RowLocation = RowLocation::new(&self.filename, &record, delimiter);
+            if stop(row + 1, parsed.as_ref().ok().copied()) {
+                break;
+            }
+
+            row += 1;
+            match parsed {
+                Ok(transaction) => {
+                    if let Some(timestamp) = transaction.get_timestamp() {
+                        max_timestamp = Some(max_timestamp.unwrap_or_default().max(timestamp));
+                    }
+                    processor.submit_transaction(transaction, Some(location));
+                }
+                Err(error) => processor.reject_row(error, Some(location)),
+            }
+
+            if processor.get_abort_reason().is_some() {
+                return Ok(processor);
+            }
+
+            let last_checkpoint: u64 = self.checkpoints.last().map_or(0, |last| last.row);
+            if row.is_multiple_of(self.interval) && row > last_checkpoint {
+                self.checkpoints.push(Checkpoint {
+                    row,
+                    position: csv_reader.position().clone(),
+                    max_timestamp,
+                    processor: processor.clone(),
+                });
+                if self.checkpoints.len() > self.max_checkpoints {
+                    self.thin_checkpoints();
+                }
+            }
+        }
+
+        // Rows still waiting in the reorder buffer are part of the state
+        processor.flush();
+        processor.check_error_rate();
+
+        Ok(processor)
+    }
+
+    // Keeps every other checkpoint and doubles the interval of the next ones
+    fn thin_checkpoints(&mut self) {
+        self.interval = self.interval.saturating_mul(2);
+        let interval: u64 = self.interval;
+        self.checkpoints
+            .retain(|checkpoint| checkpoint.row.is_multiple_of(interval));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::cid;
+
+    fn resource(file: &str) -> String {
+        format!("{}/tests/resources/{}", env!("CARGO_MANIFEST_DIR"), file)
+    }
+
+    fn records(processor: &Processor) -> Vec<Option<csv::ByteRecord>> {
+        (1..=3)
+            .map(|client_id| {
+                processor
+                    .get_client(cid(client_id))
+                    .map(|client| client.record())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_state_at_row() {
+        let filename: String = resource("disputes_example.csv");
+        let mut replayer: Replayer =
+            Replayer::new(filename.clone(), Processor::new(filename.clone()), 2);
+
+        let processor: Processor = replayer.state_at_row(3).unwrap();
+        assert_eq!(1, replayer.get_checkpoint_count());
+        assert_eq!(
+            13.5_f32,
+            processor.get_client(cid(1)).unwrap().get_available_amount()
+        );
+        assert!(processor.get_client(cid(2)).is_none());
+
+        // Resuming from a checkpoint gives the same state as a full run
+        let full: Processor = replayer.state_at_row(u64::MAX).unwrap();
+        assert_eq!(6, replayer.get_checkpoint_count());
+        let mut expected: Processor = Processor::new(filename);
+        expected.process_transactions();
+        assert_eq!(records(&expected), records(&full));
+
+        // Earlier rows don't pick up checkpoints taken later
+        assert_eq!(
+            records(&processor),
+            records(&replayer.state_at_row(3).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_state_at_row_of_compressed_input() {
+        let filename: String = resource("disputes_example.csv.gz");
+        let mut replayer: Replayer =
+            Replayer::new(filename.clone(), Processor::new(filename.clone()), 2);
+
+        let early: Processor = replayer.state_at_row(3).unwrap();
+        let full: Processor = replayer.state_at_row(u64::MAX).unwrap();
+        // Resumes from the checkpoint at row 6
+        let middle: Processor = replayer.state_at_row(7).unwrap();
+
+        let mut plain: Replayer = Replayer::new(
+            resource("disputes_example.csv"),
+            Processor::new(resource("disputes_example.csv")),
+            100,
+        );
+        assert_eq!(records(&plain.state_at_row(3).unwrap()), records(&early));
+        assert_eq!(records(&plain.state_at_row(7).unwrap()), records(&middle));
+        assert_eq!(
+            records(&plain.state_at_row(u64::MAX).unwrap()),
+            records(&full)
+        );
+    }
+
+    #[test]
+    fn test_max_checkpoints() {
+        let filename: String = resource("disputes_example.csv");
+        let mut replayer: Replayer =
+            Replayer::new(filename.clone(), Processor::new(filename.clone()), 1);
+        replayer.set_max_checkpoints(2);
+
+        // Only the checkpoint at row 8 is left, the interval went up to 8
+        let full: Processor = replayer.state_at_row(u64::MAX).unwrap();
+        assert_eq!(1, replayer.get_checkpoint_count());
+        let mut expected: Processor = Processor::new(filename.clone());
+        expected.process_transactions();
+        assert_eq!(records(&expected), records(&full));
+
+        // Resumes from the checkpoint at row 8
+        let mut plain: Replayer = Replayer::new(filename.clone(), Processor::new(filename), 100);
+        assert_eq!(
+            records(&plain.state_at_row(10).unwrap()),
+            records(&replayer.state_at_row(10).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_state_at_timestamp() {
+        let filename: String = resource("timestamps_example.csv");
+        let mut replayer: Replayer = Replayer::new(filename.clone(), Processor::new(filename), 1);
+
+        assert!(replayer
+            .state_at_timestamp(50)
+            .unwrap()
+            .get_client(cid(1))
+            .is_none());
+
+        // Replay stops at the first row after the timestamp
+        let processor: Processor = replayer.state_at_timestamp(250).unwrap();
+        assert_eq!(
+            10_f32,
+            processor.get_client(cid(1)).unwrap().get_available_amount()
+        );
+        assert!(processor.get_client(cid(2)).is_none());
+    }
+}
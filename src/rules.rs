@@ -0,0 +1,409 @@
+use crate::client::Client;
+use crate::error::p_error;
+use crate::ids::{ClientId, TxId};
+use crate::transaction::{Transaction, TxType};
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleKind {
+    // More than `limit` withdrawals of a client within `window` rows
+    WithdrawalVelocity,
+    // Withdrawal above `limit` percent of the available amount
+    WithdrawalRatio,
+    // Deposit immediately followed by a withdrawal of at least the
+    // deposited amount
+    DepositWithdrawal,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    // Transaction is processed and reported in the alerts output
+    Flag,
+    // Transaction is rejected and reported in the alerts output
+    Block,
+}
+
+impl fmt::Display for RuleKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleKind::WithdrawalVelocity => write!(f, "withdrawal_velocity"),
+            RuleKind::WithdrawalRatio => write!(f, "withdrawal_ratio"),
+            RuleKind::DepositWithdrawal => write!(f, "deposit_withdrawal"),
+        }
+    }
+}
+
+impl fmt::Display for RuleAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleAction::Flag => write!(f, "flag"),
+            RuleAction::Block => write!(f, "block"),
+        }
+    }
+}
+
+// One row of the rules file, e.g.
+//
+// rule,action,limit,window
+// withdrawal_velocity,block,3,10
+// withdrawal_ratio,flag,50,
+// deposit_withdrawal,flag,,
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Rule {
+    pub rule: RuleKind,
+    pub action: RuleAction,
+    #[serde(default)]
+    pub limit: Option<f32>,
+    #[serde(default)]
+    pub window: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub row: u64,
+    pub timestamp: Option<u64>,
+    pub client: ClientId,
+    pub tx: TxId,
+    pub rule: RuleKind,
+    pub action: RuleAction,
+}
+
+impl Alert {
+    pub fn record(&self) -> csv::ByteRecord {
+        csv::ByteRecord::from(vec![
+            format!("{}", self.row),
+            self.timestamp
+                .map(|timestamp| timestamp.to_string())
+                .unwrap_or_default(),
+            format!("{}", self.client),
+            format!("{}", self.tx),
+            self.rule.to_string(),
+            self.action.to_string(),
+        ])
+    }
+}
+
+pub fn load_rules(filename: &str) -> Result<Vec<Rule>, Box<dyn Error>> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(filename)?;
+
+    let mut rules: Vec<Rule> = Vec::new();
+    for row in csv_reader.deserialize() {
+        let rule: Rule = row?;
+
+        match rule.rule {
+            RuleKind::WithdrawalVelocity if rule.limit.is_none() || rule.window.is_none() => {
+                return p_error(format!("Rule {} needs a limit and a window.", rule.rule));
+            }
+            RuleKind::WithdrawalRatio if rule.limit.is_none() => {
+                return p_error(format!("Rule {} needs a limit.", rule.rule));
+            }
+            _ => {}
+        }
+
+        rules.push(rule);
+    }
+
+    Ok(rules)
+}
+
+// What the rules remember about one client
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientRuleState {
+    withdrawal_rows: Option<VecDeque<u64>>,
+    last_transaction: Option<Transaction>,
+}
+
+/*******************************
+< RulesEngine >
+
+Runs velocity and fraud rules on deposits and withdrawals before they
+are consumed by the client. Every triggered rule raises an alert, and
+blocking rules reject the transaction. Only transactions the client
+accepted are recorded for the rules that look at earlier ones, and only
+those keep their flag alerts.
+
+*******************************/
+#[derive(Debug, Clone, Default)]
+pub struct RulesEngine {
+    rules: Vec<Rule>,
+    // Rows of the latest withdrawals of every client
+    withdrawal_rows: HashMap<ClientId, VecDeque<u64>>,
+    // Latest deposit or withdrawal of every client
+    last_transactions: HashMap<ClientId, Transaction>,
+    alerts: Vec<Alert>,
+    // Flag alerts of the checked transaction, until the client accepts it
+    pending_alerts: Vec<Alert>,
+}
+
+impl RulesEngine {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self {
+            rules,
+            ..Self::default()
+        }
+    }
+
+    pub fn get_alerts(&self) -> &[Alert] {
+        &self.alerts
+    }
+
+    // Drops the alerts raised after the first `len` ones
+    pub fn truncate_alerts(&mut self, len: usize) {
+        self.alerts.truncate(len);
+    }
+
+    pub fn get_client_state(&self, client_id: ClientId) -> ClientRuleState {
+        ClientRuleState {
+            withdrawal_rows: self.withdrawal_rows.get(&client_id).cloned(),
+            last_transaction: self.last_transactions.get(&client_id).copied(),
+        }
+    }
+
+    pub fn set_client_state(&mut self, client_id: ClientId, state: ClientRuleState) {
+        match state.withdrawal_rows {
+            Some(withdrawal_rows) => self.withdrawal_rows.insert(client_id, withdrawal_rows),
+            None => self.withdrawal_rows.remove(&client_id),
+        };
+        match state.last_transaction {
+            Some(transaction) => self.last_transactions.insert(client_id, transaction),
+            None => self.last_transactions.remove(&client_id),
+        };
+    }
+
+    pub fn check(
+        &mut self,
+        row: u64,
+        transaction: Transaction,
+        client: &Client,
+    ) -> Result<(), Box<dyn Error>> {
+        let client_id: ClientId = transaction.get_client_id();
+        // The alerts of a transaction the client rejected are dropped
+        self.pending_alerts.clear();
+        // Only withdrawals are checked, deposits are recorded for later ones
+        if self.rules.is_empty() || transaction.get_tx_type() != TxType::Withdrawal {
+            return Ok(());
+        }
+
+        let previous: Option<&Transaction> = self.last_transactions.get(&client_id);
+        let withdrawal_rows: Option<&VecDeque<u64>> = self.withdrawal_rows.get(&client_id);
+
+        let mut blocked: Option<RuleKind> = None;
+        for rule in self.rules.iter() {
+            let triggered: bool = match rule.rule {
+                RuleKind::WithdrawalVelocity => {
+                    let window: u64 = rule.window.unwrap_or_default();
+                    // Earlier withdrawals within the window, plus this one
+                    let count: usize = withdrawal_rows.map_or(0, |withdrawal_rows| {
+                        withdrawal_rows
+                            .iter()
+                            .filter(|withdrawal_row| row - **withdrawal_row < window)
+                            .count()
+                    }) + 1;
+                    count as f32 > rule.limit.unwrap_or_default()
+                }
+                RuleKind::WithdrawalRatio => {
+                    let limit: f32 = rule.limit.unwrap_or_default() / 100_f32;
+                    transaction.get_amount() > client.get_available_amount() * limit
+                }
+                RuleKind::DepositWithdrawal => match previous {
+                    Some(deposit) if deposit.get_tx_type() == TxType::Deposit => {
+                        transaction.get_amount() >= deposit.get_amount()
+                    }
+                    _ => false,
+                },
+            };
+
+            if triggered {
+                self.pending_alerts.push(Alert {
+                    row,
+                    timestamp: transaction.get_timestamp(),
+                    client: client_id,
+                    tx: transaction.get_tx_id(),
+                    rule: rule.rule,
+                    action: rule.action,
+                });
+                if rule.action == RuleAction::Block && blocked.is_none() {
+                    blocked = Some(rule.rule);
+                }
+            }
+        }
+
+        // A blocked transaction is reported with every rule it triggered
+        if let Some(rule) = blocked {
+            self.alerts.append(&mut self.pending_alerts);
+            return p_error(format!(
+                "Transaction {} blocked by rule {}.",
+                transaction.get_tx_id(),
+                rule
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Keeps a deposit or withdrawal the client accepted, so the rules
+    // checking later transactions take it into account
+    pub fn record(&mut self, row: u64, transaction: Transaction) {
+        self.alerts.append(&mut self.pending_alerts);
+        let client_id: ClientId = transaction.get_client_id();
+        let tx_type: TxType = transaction.get_tx_type();
+        if self.rules.is_empty() || (tx_type != TxType::Deposit && tx_type != TxType::Withdrawal) {
+            return;
+        }
+
+        self.last_transactions.insert(client_id, transaction);
+        if tx_type != TxType::Withdrawal {
+            return;
+        }
+
+        let withdrawal_rows: &mut VecDeque<u64> =
+            self.withdrawal_rows.entry(client_id).or_default();
+        withdrawal_rows.push_back(row);
+
+        // Only the rows that can still fall in a velocity window are kept
+        let max_window: u64 = self
+            .rules
+            .iter()
+            .filter_map(|rule| rule.window)
+            .max()
+            .unwrap_or_default();
+        while let Some(oldest) = withdrawal_rows.front() {
+            if row - oldest < max_window {
+                break;
+            }
+            withdrawal_rows.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::{cid, tid};
+
+    fn withdrawal(tx: TxId, amount: f32) -> Transaction {
+        Transaction {
+            tx_type: TxType::Withdrawal,
+            client: cid(1),
+            tx,
+            amount: Some(amount),
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn test_withdrawal_velocity() {
+        let mut engine: RulesEngine = RulesEngine::new(vec![Rule {
+            rule: RuleKind::WithdrawalVelocity,
+            action: RuleAction::Block,
+            limit: Some(2_f32),
+            window: Some(5),
+        }]);
+        let client: Client = Client::new(cid(1));
+
+        for (row, tx) in [(1, 1), (2, 2)] {
+            assert_eq!(
+                (),
+                engine
+                    .check(row, withdrawal(tid(tx), 1_f32), &client)
+                    .unwrap()
+            );
+            engine.record(row, withdrawal(tid(tx), 1_f32));
+        }
+        assert_eq!(
+            "PROCESSOR ERROR: Transaction 3 blocked by rule withdrawal_velocity.",
+            engine
+                .check(5, withdrawal(tid(3), 1_f32), &client)
+                .unwrap_err()
+                .to_string()
+        );
+        // Row 1 falls out of the window and the blocked withdrawal
+        // isn't counted
+        assert_eq!(
+            (),
+            engine.check(6, withdrawal(tid(4), 1_f32), &client).unwrap()
+        );
+        assert_eq!(1, engine.get_alerts().len());
+    }
+
+    #[test]
+    fn test_withdrawal_ratio_and_deposit_withdrawal() {
+        let mut engine: RulesEngine = RulesEngine::new(vec![
+            Rule {
+                rule: RuleKind::WithdrawalRatio,
+                action: RuleAction::Flag,
+                limit: Some(50_f32),
+                window: None,
+            },
+            Rule {
+                rule: RuleKind::DepositWithdrawal,
+                action: RuleAction::Flag,
+                limit: None,
+                window: None,
+            },
+        ]);
+        let mut client: Client = Client::new(cid(1));
+        client.increase_available_amount(10_f32);
+
+        let deposit: Transaction = Transaction {
+            tx_type: TxType::Deposit,
+            client: cid(1),
+            tx: tid(1),
+            amount: Some(4_f32),
+            timestamp: None,
+        };
+        assert_eq!((), engine.check(1, deposit, &client).unwrap());
+        // A deposit the client rejected isn't followed by the withdrawal
+        assert_eq!(
+            (),
+            engine.check(2, withdrawal(tid(2), 4_f32), &client).unwrap()
+        );
+        engine.record(1, deposit);
+        assert_eq!(
+            (),
+            engine.check(2, withdrawal(tid(2), 4_f32), &client).unwrap()
+        );
+        engine.record(2, withdrawal(tid(2), 4_f32));
+        // The flag alert of a withdrawal the client rejected is dropped
+        assert_eq!(
+            (),
+            engine
+                .check(3, withdrawal(tid(3), 60_f32), &client)
+                .unwrap()
+        );
+        assert_eq!(
+            (),
+            engine.check(4, withdrawal(tid(4), 6_f32), &client).unwrap()
+        );
+        engine.record(4, withdrawal(tid(4), 6_f32));
+
+        assert_eq!(
+            vec![
+                Alert {
+                    row: 2,
+                    timestamp: None,
+                    client: cid(1),
+                    tx: tid(2),
+                    rule: RuleKind::DepositWithdrawal,
+                    action: RuleAction::Flag,
+                },
+                Alert {
+                    row: 4,
+                    timestamp: None,
+                    client: cid(1),
+                    tx: tid(4),
+                    rule: RuleKind::WithdrawalRatio,
+                    action: RuleAction::Flag,
+                },
+            ],
+            engine.get_alerts()
+        );
+    }
+}
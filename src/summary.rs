@@ -0,0 +1,36 @@
+use std::fmt;
+
+// Counts of a processing run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunSummary {
+    // Input rows read, including the rejected and skipped ones
+    pub rows: u64,
+    // Rows that couldn't be parsed or were rejected by the processor
+    pub rejected: u64,
+    // Exact duplicates of rows ingested before
+    pub duplicate_rows: u64,
+    // Input files with the same content as a file ingested before
+    pub duplicate_files: u64,
+}
+
+impl RunSummary {
+    // Counts added since an earlier summary of the same run
+    pub fn since(&self, earlier: RunSummary) -> RunSummary {
+        RunSummary {
+            rows: self.rows - earlier.rows,
+            rejected: self.rejected - earlier.rejected,
+            duplicate_rows: self.duplicate_rows - earlier.duplicate_rows,
+            duplicate_files: self.duplicate_files - earlier.duplicate_files,
+        }
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} row(s) read, {} rejected, {} duplicate row(s) and {} duplicate file(s) skipped.",
+            self.rows, self.rejected, self.duplicate_rows, self.duplicate_files
+        )
+    }
+}
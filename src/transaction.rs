@@ -1,6 +1,16 @@
-use serde::Deserialize;
+use crate::error::p_error;
+use crate::ids::{ClientId, TxId};
+use crate::ledger::to_minor_units;
+use serde::de::Error as DeError;
+use serde::{Deserialize, Deserializer};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+// Amounts are kept in ten-thousandths
+pub const AMOUNT_DECIMALS: usize = 4;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum TxType {
     Deposit,
@@ -8,58 +18,240 @@ pub enum TxType {
     Dispute,
     Resolve,
     Chargeback,
+    // Undoes an earlier deposit or withdrawal
+    Reversal,
+    // Holds funds for a later capture
+    Authorize,
+    // Debits the funds held by an authorization
+    Capture,
+    // Releases the funds held by an authorization
+    Void,
+}
+
+impl TxType {
+    // Deposits, withdrawals and authorizations move funds and need an
+    // amount, the other transactions refer to an earlier one. Captures
+    // refer to an authorization and may give the amount to debit.
+    pub fn has_amount(self) -> bool {
+        matches!(
+            self,
+            TxType::Deposit | TxType::Withdrawal | TxType::Authorize | TxType::Capture
+        )
+    }
+
+    // Captures without an amount debit the whole authorization
+    pub fn has_optional_amount(self) -> bool {
+        self == TxType::Capture
+    }
+}
+
+impl fmt::Display for TxType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TxType::Deposit => write!(f, "deposit"),
+            TxType::Withdrawal => write!(f, "withdrawal"),
+            TxType::Dispute => write!(f, "dispute"),
+            TxType::Resolve => write!(f, "resolve"),
+            TxType::Chargeback => write!(f, "chargeback"),
+            TxType::Reversal => write!(f, "reversal"),
+            TxType::Authorize => write!(f, "authorize"),
+            TxType::Capture => write!(f, "capture"),
+            TxType::Void => write!(f, "void"),
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
 pub struct Transaction {
     #[serde(rename = "type")]
     pub tx_type: TxType,
-    pub client: u16,
-    pub tx: u32,
+    pub client: ClientId,
+    pub tx: TxId,
 
-    #[serde(default = "default_amount")]
-    pub amount: f32,
+    // Absent for transactions that refer to an earlier one, and for
+    // captures of a whole authorization
+    #[serde(default, deserialize_with = "deserialize_amount")]
+    pub amount: Option<f32>,
+
+    // Event time, in seconds since the Unix epoch
+    #[serde(default)]
+    pub timestamp: Option<u64>,
 }
 
-// For 3 column rows that don't have amount
-pub fn default_amount() -> f32 {
-    0_f32
+// Rows with a timestamp column keep an empty amount column for
+// dispute/resolve/chargeback transactions
+fn deserialize_amount<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f32>, D::Error> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(amount) if !amount.trim().is_empty() => parse_amount(amount.trim())
+            .map(Some)
+            .map_err(D::Error::custom),
+        _ => Ok(None),
+    }
+}
+
+// Parses a plain decimal amount with at most 4 decimal places. NaN,
+// infinity and exponent notation are refused, even though f32 parses them.
+pub fn parse_amount(amount: &str) -> Result<f32, String> {
+    let lowercase: String = amount.to_lowercase();
+    let unsigned: &str = lowercase.strip_prefix(['-', '+']).unwrap_or(&lowercase);
+
+    if ["nan", "inf", "infinity"].contains(&unsigned) {
+        return Err(format!("Amount {} is not a finite number.", amount));
+    }
+
+    let (integer, decimals): (&str, &str) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if !is_digits(integer) || !is_digits(decimals) || (integer.is_empty() && decimals.is_empty()) {
+        if unsigned.contains('e') && unsigned.parse::<f32>().is_ok() {
+            return Err(format!("Amount {} uses exponent notation.", amount));
+        }
+        return Err(format!("Amount {} is not a decimal number.", amount));
+    }
+    if decimals.len() > AMOUNT_DECIMALS {
+        return Err(format!(
+            "Amount {} has more than {} decimal places.",
+            amount, AMOUNT_DECIMALS
+        ));
+    }
+
+    let parsed: f32 = amount
+        .parse::<f32>()
+        .map_err(|_| format!("Amount {} is not a decimal number.", amount))?;
+
+    // Amounts are kept as f32, so large amounts lose their last decimal
+    // places. Those are refused instead of being changed silently.
+    let exact: Option<i64> = format!("{}{:0<width$}", integer, decimals, width = AMOUNT_DECIMALS)
+        .parse::<i64>()
+        .ok();
+    if exact != Some(to_minor_units(parsed).abs()) {
+        return Err(format!(
+            "Amount {} can't be represented with {} decimal places.",
+            amount, AMOUNT_DECIMALS
+        ));
+    }
+
+    Ok(parsed)
+}
+
+// What happens to an amount given to a dispute, resolve, chargeback,
+// reversal or void
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnexpectedAmount {
+    // The row is rejected
+    #[default]
+    Reject,
+    // The amount is dropped with a warning
+    Ignore,
+}
+
+impl FromStr for UnexpectedAmount {
+    type Err = Box<dyn Error>;
+
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        match action {
+            "reject" => Ok(UnexpectedAmount::Reject),
+            "ignore" => Ok(UnexpectedAmount::Ignore),
+            _ => p_error(format!(
+                "Unknown unexpected amount action {}. Use reject or ignore.",
+                action
+            )),
+        }
+    }
 }
 
 impl Transaction {
-    pub fn get_tx_id(self) -> u32 {
+    pub fn get_tx_id(self) -> TxId {
         self.tx
     }
 
-    pub fn get_client_id(self) -> u16 {
+    pub fn get_client_id(self) -> ClientId {
         self.client
     }
 
+    // 0 for transactions without an amount
     pub fn get_amount(self) -> f32 {
-        self.amount
+        self.amount.unwrap_or_default()
     }
 
     pub fn get_tx_type(self) -> TxType {
         self.tx_type
     }
+
+    pub fn get_timestamp(self) -> Option<u64> {
+        self.timestamp
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ids::{cid, tid};
 
     #[test]
     fn test_new_transaction() {
         let transaction: Transaction = Transaction {
             tx_type: TxType::Deposit,
-            client: 1,
-            tx: 1,
-            amount: 10.0456_f32,
+            client: cid(1),
+            tx: tid(1),
+            amount: Some(10.0456_f32),
+            timestamp: None,
         };
 
-        assert_eq!(transaction.get_tx_id(), 1);
-        assert_eq!(transaction.get_client_id(), 1);
+        assert_eq!(transaction.get_tx_id(), tid(1));
+        assert_eq!(transaction.get_client_id(), cid(1));
         assert_eq!(transaction.get_tx_type(), TxType::Deposit);
         assert_eq!(transaction.get_amount(), 10.0456_f32);
     }
+
+    #[test]
+    #[cfg(not(any(feature = "wide-ids", feature = "string-ids")))]
+    fn test_default_transaction_size() {
+        assert_eq!(32, std::mem::size_of::<Transaction>());
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(Ok(10.0456_f32), parse_amount("10.0456"));
+        assert_eq!(Ok(3_f32), parse_amount("3"));
+        assert_eq!(Ok(0.5_f32), parse_amount(".5"));
+        assert_eq!(Ok(-2_f32), parse_amount("-2.0"));
+        assert_eq!(
+            Err("Amount 1.00001 has more than 4 decimal places.".to_string()),
+            parse_amount("1.00001")
+        );
+        assert_eq!(
+            Err("Amount NaN is not a finite number.".to_string()),
+            parse_amount("NaN")
+        );
+        assert_eq!(
+            Err("Amount -inf is not a finite number.".to_string()),
+            parse_amount("-inf")
+        );
+        assert_eq!(
+            Err("Amount 1e3 uses exponent notation.".to_string()),
+            parse_amount("1e3")
+        );
+        assert_eq!(
+            Err("Amount 1,5 is not a decimal number.".to_string()),
+            parse_amount("1,5")
+        );
+        assert_eq!(
+            Err("Amount . is not a decimal number.".to_string()),
+            parse_amount(".")
+        );
+        // f32 keeps about 7 significant digits
+        assert_eq!(Ok(123.4567_f32), parse_amount("123.4567"));
+        assert_eq!(Ok(-123456.5_f32), parse_amount("-123456.5"));
+        assert_eq!(
+            Err("Amount 12345.6789 can't be represented with 4 decimal places.".to_string()),
+            parse_amount("12345.6789")
+        );
+        assert_eq!(
+            Err(
+                "Amount 99999999999999999999 can't be represented with 4 decimal places."
+                    .to_string()
+            ),
+            parse_amount("99999999999999999999")
+        );
+    }
 }
@@ -1,4 +1,5 @@
 use assert_cmd::prelude::*; // Add methods on commands
+use predicates::prelude::*;
 use std::process::Command; // Run programs
 
 #[test]
@@ -26,9 +27,9 @@ fn test_argument_not_provided() -> Result<(), Box<dyn std::error::Error>> {
 fn test_multiple_arguments_provided() -> Result<(), Box<dyn std::error::Error>> {
     let mut cmd = Command::cargo_bin("toy_processor")?;
     cmd.arg("one").arg("two");
-    cmd.assert().failure().stderr(predicates::str::contains(
-        "There should be only one argument given to the program.",
-    ));
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains("File one does not exist."));
 
     Ok(())
 }
@@ -43,7 +44,7 @@ fn test_basic_csv_example() -> Result<(), Box<dyn std::error::Error>> {
     ));
 
     cmd.assert()
-        .success()
+        .code(4)
         .stdout(predicates::str::contains(
             "client,available,held,total,locked",
         ))
@@ -63,7 +64,7 @@ fn test_csv_example_with_errors() -> Result<(), Box<dyn std::error::Error>> {
     ));
 
     cmd.assert()
-        .success()
+        .code(4)
         .stderr(predicates::str::contains("PROCESSOR ERROR: Invalid withdrawal transaction 5. Available amount is smaller than withdraw amount."))
         .stderr(predicates::str::contains("PROCESSOR ERROR: Invalid withdrawal transaction 6. Available amount is smaller than withdraw amount."))
         .stdout(predicates::str::contains(
@@ -95,3 +96,1168 @@ fn test_stress_csv_example() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_verify_mode() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+
+    cmd.arg(format!(
+        "{}/tests/resources/disputes_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--verify");
+
+    cmd.assert()
+        .success()
+        .stderr(predicates::str::contains("Verification passed."))
+        .stdout(predicates::str::contains("1,10.0000,5.5000,15.5000,true"))
+        .stdout(predicates::str::contains("2,7.2500,0.0000,7.2500,false"))
+        .stdout(predicates::str::contains("3,0.0000,0.0000,0.0000,true"));
+
+    Ok(())
+}
+
+#[test]
+fn test_unknown_option() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg("file.csv").arg("--unknown");
+    cmd.assert().failure().stderr(predicates::str::contains(
+        "unexpected argument '--unknown' found",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_credit_limits_and_overdraft_report() -> Result<(), Box<dyn std::error::Error>> {
+    let report: String = format!("{}/overdraft_report.csv", env!("CARGO_TARGET_TMPDIR"));
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+
+    cmd.arg(format!(
+        "{}/tests/resources/overdraft_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--client-config")
+    .arg(format!(
+        "{}/tests/resources/credit_limits.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--overdraft-report")
+    .arg(&report);
+
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains("PROCESSOR ERROR: Invalid withdrawal transaction 3. Available amount is smaller than withdraw amount."))
+        .stderr(predicates::str::contains("PROCESSOR ERROR: Invalid withdrawal transaction 8. Available amount is smaller than withdraw amount."))
+        .stdout(predicates::str::contains("1,1.0000,0.0000,1.0000,false"))
+        .stdout(predicates::str::contains("2,-0.2500,0.0000,-0.2500,false"))
+        .stdout(predicates::str::contains("3,1.0000,0.0000,1.0000,false"));
+
+    assert_eq!(
+        "client,available,credit_limit,overdraft,peak_overdraft\n\
+         2,-0.2500,0.5000,0.2500,0.2500\n",
+        std::fs::read_to_string(&report)?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_client_registry_strict_clients() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+
+    cmd.arg(format!(
+        "{}/tests/resources/registry_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--client-config")
+    .arg(format!(
+        "{}/tests/resources/client_registry.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--strict-clients")
+    .arg("--client-details");
+
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains(
+            "PROCESSOR ERROR: Client 2 is frozen and cannot accept withdrawal 4.",
+        ))
+        .stderr(predicates::str::contains(
+            "PROCESSOR ERROR: Client 3 is closed and cannot accept transaction 5.",
+        ))
+        .stderr(predicates::str::contains(
+            "PROCESSOR ERROR: Client 4 of transaction 6 is not registered.",
+        ))
+        .stdout(predicates::str::contains(
+            "client,available,held,total,locked,name,status",
+        ))
+        .stdout(predicates::str::contains(
+            "1,-3.5000,0.0000,-3.5000,false,Jane Doe,active",
+        ))
+        .stdout(predicates::str::contains(
+            "2,12.0000,0.0000,12.0000,false,John Smith,frozen",
+        ))
+        .stdout(predicates::str::contains(
+            "3,0.0000,0.0000,0.0000,false,,closed",
+        ))
+        .stdout(predicates::str::contains("4,").not());
+
+    Ok(())
+}
+
+#[test]
+fn test_strict_clients_requires_registry() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!(
+        "{}/tests/resources/basic_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--strict-clients");
+    cmd.assert().failure().stderr(predicates::str::contains(
+        "Option --strict-clients requires --client-config.",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_opening_balances_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let opening_balances: String = format!("{}/opening_balances.csv", env!("CARGO_TARGET_TMPDIR"));
+
+    // The funds of the open dispute of client 1 are carried over as held
+    let first_run = Command::cargo_bin("toy_processor")?
+        .arg(format!(
+            "{}/tests/resources/disputes_example.csv",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .output()?;
+    assert!(String::from_utf8_lossy(&first_run.stdout).contains("1,10.0000,5.5000,15.5000,true"));
+    std::fs::write(&opening_balances, &first_run.stdout)?;
+
+    let second_run = Command::cargo_bin("toy_processor")?
+        .arg(format!(
+            "{}/tests/resources/header_only.csv",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .arg("--opening-balances")
+        .arg(&opening_balances)
+        .arg("--verify")
+        .output()?;
+    assert!(second_run.status.success());
+
+    // Clients are printed in no particular order
+    let sorted_lines = |output: &[u8]| -> Vec<String> {
+        let mut lines: Vec<String> = String::from_utf8_lossy(output)
+            .lines()
+            .map(String::from)
+            .collect();
+        lines.sort();
+        lines
+    };
+    assert_eq!(
+        sorted_lines(&first_run.stdout),
+        sorted_lines(&second_run.stdout)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_opening_balances_precedence() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+
+    // Client 1 is opened from the opening balances instead of the registry
+    cmd.arg(format!(
+        "{}/tests/resources/header_only.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--client-config")
+    .arg(format!(
+        "{}/tests/resources/client_registry.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--opening-balances")
+    .arg(format!(
+        "{}/tests/resources/opening_balances_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--verify");
+
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("1,2.0000,0.0000,2.0000,false"))
+        .stdout(predicates::str::contains("2,10.0000,0.0000,10.0000,false"));
+
+    // Held funds are seeded as they are
+    let opening_balances: String =
+        format!("{}/held_opening_balances.csv", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(
+        &opening_balances,
+        "client,available,held,total,locked\n1,1.0000,2.0000,3.0000,false\n",
+    )?;
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!(
+        "{}/tests/resources/header_only.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--opening-balances")
+    .arg(&opening_balances)
+    .arg("--verify");
+
+    cmd.assert()
+        .success()
+        .stderr(predicates::str::contains("Verification passed."))
+        .stdout(predicates::str::contains("1,1.0000,2.0000,3.0000,false"));
+
+    Ok(())
+}
+
+#[test]
+fn test_rules_and_alerts() -> Result<(), Box<dyn std::error::Error>> {
+    let alerts: String = format!("{}/alerts.csv", env!("CARGO_TARGET_TMPDIR"));
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+
+    cmd.arg(format!(
+        "{}/tests/resources/rules_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--rules")
+    .arg(format!(
+        "{}/tests/resources/rules.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--alerts")
+    .arg(&alerts);
+
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains(
+            "PROCESSOR ERROR: Transaction 4 blocked by rule withdrawal_velocity.",
+        ))
+        .stdout(predicates::str::contains("1,80.0000,0.0000,80.0000,false"))
+        .stdout(predicates::str::contains("2,0.0000,0.0000,0.0000,false"));
+
+    assert_eq!(
+        "row,timestamp,client,tx,rule,action\n\
+         4,,1,4,withdrawal_velocity,block\n\
+         6,,2,6,withdrawal_ratio,flag\n\
+         6,,2,6,deposit_withdrawal,flag\n",
+        std::fs::read_to_string(&alerts)?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_lock_policies_and_risk_report() -> Result<(), Box<dyn std::error::Error>> {
+    let risk_report: String = format!("{}/risk_report.csv", env!("CARGO_TARGET_TMPDIR"));
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+
+    cmd.arg(format!(
+        "{}/tests/resources/risk_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--policy")
+    .arg(format!(
+        "{}/tests/resources/policy.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--risk-report")
+    .arg(&risk_report)
+    .arg("--verify");
+
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains(
+            "PROCESSOR ERROR: Locked accounts cannot accept withdrawals.",
+        ))
+        .stderr(predicates::str::contains("Verification passed."))
+        .stdout(predicates::str::contains("1,1.0000,2.0000,3.0000,true"));
+
+    assert_eq!(
+        "client,deposits,disputes,chargebacks,open_disputes,dispute_ratio,chargeback_ratio,locked,lock_reason\n\
+         1,3,2,0,2,0.6667,0.0000,true,open_disputes\n\
+         2,1,1,0,0,1.0000,0.0000,false,\n\
+         3,1,0,0,0,0.0000,0.0000,false,\n",
+        std::fs::read_to_string(&risk_report)?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_dispute_deadlines() -> Result<(), Box<dyn std::error::Error>> {
+    let synthetic_events: String = format!("{}/synthetic_events.csv", env!("CARGO_TARGET_TMPDIR"));
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+
+    cmd.arg(format!(
+        "{}/tests/resources/dispute_deadlines_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--dispute-deadline")
+    .arg("3")
+    .arg("--on-dispute-expiry")
+    .arg("chargeback")
+    .arg("--synthetic-events")
+    .arg(&synthetic_events);
+
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("1,2.0000,0.0000,2.0000,true"))
+        .stdout(predicates::str::contains("2,4.0000,0.0000,4.0000,false"));
+
+    assert_eq!(
+        "row,type,client,tx,timestamp\n6,chargeback,1,1,\n",
+        std::fs::read_to_string(&synthetic_events)?
+    );
+
+    // Disputes whose deadline hasn't passed at the end of the input stay open
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!(
+        "{}/tests/resources/dispute_deadlines_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--dispute-deadline")
+    .arg("10")
+    .arg("--on-dispute-expiry")
+    .arg("chargeback");
+
+    cmd.assert()
+        .success()
+        .stderr(predicates::str::contains("EXPIRED DISPUTE").not())
+        .stderr(predicates::str::contains(
+            "PENDING DISPUTE: Transaction 1 of client 1 is still open, its deadline passes at row 13.",
+        ))
+        .stdout(predicates::str::contains("1,2.0000,5.0000,7.0000,false"));
+
+    Ok(())
+}
+
+#[test]
+fn test_event_time_ordering() -> Result<(), Box<dyn std::error::Error>> {
+    let synthetic_events: String = format!(
+        "{}/timestamp_synthetic_events.csv",
+        env!("CARGO_TARGET_TMPDIR")
+    );
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+
+    cmd.arg(format!(
+        "{}/tests/resources/timestamps_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--reorder-window")
+    .arg("2")
+    .arg("--dispute-deadline")
+    .arg("600")
+    .arg("--dispute-deadline-unit")
+    .arg("seconds")
+    .arg("--on-dispute-expiry")
+    .arg("chargeback")
+    .arg("--synthetic-events")
+    .arg(&synthetic_events);
+
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains(
+            "PROCESSOR ERROR: Transaction 4 arrived too late. Timestamp 50 is older than 200.",
+        ))
+        .stdout(predicates::str::contains("1,6.0000,0.0000,6.0000,true"))
+        .stdout(predicates::str::contains("2,5.0000,0.0000,5.0000,false"));
+
+    assert_eq!(
+        "row,type,client,tx,timestamp\n6,chargeback,1,3,1000\n",
+        std::fs::read_to_string(&synthetic_events)?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_deadlines_past_the_end_of_the_clock() -> Result<(), Box<dyn std::error::Error>> {
+    let file: String = format!("{}/max_timestamp.csv", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(
+        &file,
+        "type,client,tx,amount,timestamp\n\
+         deposit,1,1,5.0,18446744073709551615\n\
+         dispute,1,1,,18446744073709551615\n\
+         deposit,2,2,5.0,18446744073709551615\n\
+         authorize,2,3,1.0,18446744073709551615\n",
+    )?;
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+
+    // The deadlines would pass after the last timestamp, so they never do
+    cmd.arg(&file)
+        .arg("--dispute-deadline")
+        .arg("10")
+        .arg("--dispute-deadline-unit")
+        .arg("seconds")
+        .arg("--authorization-expiry")
+        .arg("10")
+        .arg("--authorization-expiry-unit")
+        .arg("seconds");
+
+    cmd.assert()
+        .success()
+        .stderr(predicates::str::contains("EXPIRED").not())
+        .stdout(predicates::str::contains("1,0.0000,5.0000,5.0000,false"))
+        .stdout(predicates::str::contains("2,4.0000,1.0000,5.0000,false"));
+
+    Ok(())
+}
+
+#[test]
+fn test_point_in_time_balances() -> Result<(), Box<dyn std::error::Error>> {
+    let input: String = format!(
+        "{}/tests/resources/disputes_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    );
+
+    let dedup_state: String = format!("{}/replay_dedup_state.csv", env!("CARGO_TARGET_TMPDIR"));
+    let _ = std::fs::remove_file(&dedup_state);
+
+    // Replays don't record the rows they read
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(&input)
+        .arg("--as-of-row")
+        .arg("3")
+        .arg("--dedup-state")
+        .arg(&dedup_state);
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("1,13.5000,0.0000,13.5000,false"))
+        .stdout(predicates::str::contains("2,").not());
+    assert!(!std::path::Path::new(&dedup_state).exists());
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!(
+        "{}/tests/resources/timestamps_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--as-of-timestamp")
+    .arg("250");
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("1,10.0000,0.0000,10.0000,false"));
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(&input)
+        .arg("--as-of-row")
+        .arg("3")
+        .arg("--as-of-timestamp")
+        .arg("250");
+    cmd.assert().failure().stderr(predicates::str::contains(
+        "Options --as-of-row and --as-of-timestamp can't be used together.",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_duplicate_rows_and_files() -> Result<(), Box<dyn std::error::Error>> {
+    let input: String = format!(
+        "{}/tests/resources/duplicates_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let dedup_state: String = format!("{}/dedup_state.csv", env!("CARGO_TARGET_TMPDIR"));
+    let _ = std::fs::remove_file(&dedup_state);
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(&input)
+        .arg("--dedup-state")
+        .arg(&dedup_state)
+        .arg("--summary");
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains(
+            "PROCESSOR ERROR: Transaction 2 conflicts with an earlier deposit row with different content.",
+        ))
+        .stderr(predicates::str::contains(
+            "SUMMARY: 9 row(s) read, 1 rejected, 3 duplicate row(s) and 0 duplicate file(s) skipped.",
+        ))
+        .stdout(predicates::str::contains("1,14.0000,0.0000,14.0000,false"));
+
+    // The same file delivered again is skipped as a whole
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(&input)
+        .arg("--dedup-state")
+        .arg(&dedup_state)
+        .arg("--summary");
+    cmd.assert()
+        .success()
+        .stderr(predicates::str::contains(
+            "SUMMARY: 0 row(s) read, 0 rejected, 0 duplicate row(s) and 1 duplicate file(s) skipped.",
+        ))
+        .stdout(predicates::str::contains("1,").not());
+
+    Ok(())
+}
+
+#[test]
+fn test_redelivered_rejected_rows() -> Result<(), Box<dyn std::error::Error>> {
+    let first: String = format!("{}/redelivery_01.csv", env!("CARGO_TARGET_TMPDIR"));
+    let second: String = format!("{}/redelivery_02.csv", env!("CARGO_TARGET_TMPDIR"));
+    let dedup_state: String = format!("{}/redelivery_state.csv", env!("CARGO_TARGET_TMPDIR"));
+    let _ = std::fs::remove_file(&dedup_state);
+    std::fs::write(
+        &first,
+        "type, client, tx, amount\n\
+         withdrawal, 1, 2, 4.0\n\
+         deposit, 1, 1, 3.0\n",
+    )?;
+    std::fs::write(
+        &second,
+        "type, client, tx, amount\n\
+         deposit, 1, 3, 2.0\n\
+         withdrawal, 1, 2, 4.0\n",
+    )?;
+
+    // The rejected withdrawal isn't remembered as ingested
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(&first)
+        .arg("--dedup-state")
+        .arg(&dedup_state)
+        .arg("--summary");
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains(
+            "SUMMARY: 2 row(s) read, 1 rejected, 0 duplicate row(s) and 0 duplicate file(s) skipped.",
+        ))
+        .stdout(predicates::str::contains("1,3.0000,0.0000,3.0000,false"));
+
+    // Sent again once the funds are there, it goes through
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(&second)
+        .arg("--opening-balances")
+        .arg(format!(
+            "{}/tests/resources/opening_balances_example.csv",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .arg("--dedup-state")
+        .arg(&dedup_state)
+        .arg("--summary");
+    cmd.assert()
+        .success()
+        .stderr(predicates::str::contains(
+            "SUMMARY: 2 row(s) read, 0 rejected, 0 duplicate row(s) and 0 duplicate file(s) skipped.",
+        ))
+        .stdout(predicates::str::contains("1,0.0000,0.0000,0.0000,false"));
+
+    Ok(())
+}
+
+#[test]
+fn test_dry_run() -> Result<(), Box<dyn std::error::Error>> {
+    let rejects: String = format!("{}/dry_run_rejects.csv", env!("CARGO_TARGET_TMPDIR"));
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+
+    cmd.arg(format!(
+        "{}/tests/resources/dry_run_batch.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--opening-balances")
+    .arg(format!(
+        "{}/tests/resources/dry_run_state.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--dry-run")
+    .arg("--rejects")
+    .arg(&rejects);
+
+    cmd.assert()
+        .code(4)
+        .stdout(predicates::str::diff(
+            "client,available_before,available_after,held_before,held_after,total_before,total_after,locked_before,locked_after\n\
+             1,10.0000,12.5000,0.0000,0.0000,10.0000,12.5000,false,false\n\
+             3,1.0000,0.0000,0.0000,0.0000,1.0000,0.0000,false,false\n\
+             4,,3.0000,,0.0000,,3.0000,,false\n",
+        ))
+        .stderr(predicates::str::contains(
+            "PROCESSOR ERROR: Invalid withdrawal transaction 2. Available amount is smaller than withdraw amount.",
+        ))
+        .stderr(predicates::str::contains(
+            "DRY RUN: 4 row(s) read, 1 rejected, 0 duplicate row(s) and 0 duplicate file(s) skipped.",
+        ));
+
+    let rejects: String = std::fs::read_to_string(&rejects)?;
+    assert!(rejects.starts_with("type,client,tx,amount,timestamp,error,file,line,byte,row\n"));
+    assert!(rejects.contains(
+        "withdrawal,2,2,8.0000,,PROCESSOR ERROR: Invalid withdrawal transaction 2. Available amount is smaller than withdraw amount."
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_command() -> Result<(), Box<dyn std::error::Error>> {
+    let legacy: String = format!(
+        "{}/tests/resources/accounts_legacy.csv",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let current: String = format!(
+        "{}/tests/resources/accounts_current.csv",
+        env!("CARGO_MANIFEST_DIR")
+    );
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg("diff").arg(&legacy).arg(&current);
+    cmd.assert()
+        .failure()
+        .stdout(predicates::str::diff(
+            "client,available_before,available_after,held_before,held_after,total_before,total_after,locked_before,locked_after\n\
+             1,10.0000,,0.0000,,10.0000,,false,\n\
+             3,1.0000,1.0040,0.0000,0.0000,1.0000,1.0040,true,false\n\
+             4,,2.0000,,0.0000,,2.0000,,false\n",
+        ))
+        .stderr(predicates::str::contains("3 client(s) differ."));
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg("diff")
+        .arg(&legacy)
+        .arg(&current)
+        .arg("--tolerance")
+        .arg("0.005")
+        .arg("--ignore-locked");
+    cmd.assert()
+        .failure()
+        .stdout(predicates::str::contains("\n3,").not())
+        .stderr(predicates::str::contains("2 client(s) differ."));
+
+    // Identical files
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg("diff").arg(&legacy).arg(&legacy);
+    cmd.assert().success().stdout(predicates::str::diff(
+        "client,available_before,available_after,held_before,held_after,total_before,total_after,locked_before,locked_after\n",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_help() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg("--help");
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("Usage: toy_processor"))
+        .stdout(predicates::str::contains("replay"))
+        .stdout(predicates::str::contains("--rejects <FILE>"));
+
+    Ok(())
+}
+
+#[test]
+fn test_subcommands() -> Result<(), Box<dyn std::error::Error>> {
+    let input: String = format!(
+        "{}/tests/resources/disputes_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    );
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg("process").arg(&input);
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("1,10.0000,5.5000,15.5000,true"));
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg("verify").arg(&input);
+    cmd.assert()
+        .success()
+        .stderr(predicates::str::contains("Verification passed."));
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg("stats").arg(&input);
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "metric,value\nrows,12\nrejected,0\n",
+        ))
+        .stdout(predicates::str::contains("locked_clients,2\n"))
+        .stdout(predicates::str::contains("total,22.7500\n"));
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg("replay").arg(&input).arg("--as-of-row").arg("3");
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("1,13.5000,0.0000,13.5000,false"));
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg("replay").arg(&input);
+    cmd.assert().failure().stderr(predicates::str::contains(
+        "Command replay requires --as-of-row or --as-of-timestamp.",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_formats_output_and_rejects() -> Result<(), Box<dyn std::error::Error>> {
+    let output: String = format!("{}/accounts.tsv", env!("CARGO_TARGET_TMPDIR"));
+    let rejects: String = format!("{}/rejects.csv", env!("CARGO_TARGET_TMPDIR"));
+    let input: String = format!(
+        "{}/tests/resources/example_with_errors.tsv",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+
+    cmd.arg(&input)
+        .arg("--input-format")
+        .arg("tsv")
+        .arg("--output-format")
+        .arg("tsv")
+        .arg("--output")
+        .arg(&output)
+        .arg("--rejects")
+        .arg(&rejects);
+
+    cmd.assert()
+        .code(4)
+        .stdout(predicates::str::is_empty())
+        .stderr(predicates::str::contains(format!(
+            "{}:7 (byte 108): Row has 1 field(s) and no client column. Row: withdrawal,2,6,1.0",
+            input
+        )));
+
+    let accounts: String = std::fs::read_to_string(&output)?;
+    assert!(accounts.starts_with("client\tavailable\theld\ttotal\tlocked\n"));
+    assert!(accounts.contains("1\t1.5000\t0.0000\t1.5000\tfalse\n"));
+    assert_eq!(
+        format!(
+            "type,client,tx,amount,timestamp,error,file,line,byte,row\n\
+             withdrawal,2,5,3.0000,,PROCESSOR ERROR: Invalid withdrawal transaction 5. Available amount is smaller than withdraw amount.,{input},6,89,withdrawal\t2\t5\t3.0\n\
+             ,,,,,Row has 1 field(s) and no client column.,{input},7,108,\"withdrawal,2,6,1.0\"\n",
+            input = input
+        ),
+        std::fs::read_to_string(&rejects)?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_multiple_input_files() -> Result<(), Box<dyn std::error::Error>> {
+    let shards: String = format!("{}/tests/resources/shards", env!("CARGO_MANIFEST_DIR"));
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(&shards).arg("--summary");
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains(format!(
+            "SUMMARY: {}/01.csv: 2 row(s) read, 0 rejected, 0 duplicate row(s) and 0 duplicate file(s) skipped.",
+            shards
+        )))
+        .stderr(predicates::str::contains(format!(
+            "SUMMARY: {}/02.csv: 3 row(s) read, 1 rejected, 0 duplicate row(s) and 0 duplicate file(s) skipped.",
+            shards
+        )))
+        .stderr(predicates::str::contains(format!(
+            "SUMMARY: {}/03.csv: 3 row(s) read, 0 rejected, 1 duplicate row(s) and 0 duplicate file(s) skipped.",
+            shards
+        )))
+        .stderr(predicates::str::contains(
+            "SUMMARY: 8 row(s) read, 1 rejected, 1 duplicate row(s) and 0 duplicate file(s) skipped.",
+        ))
+        .stdout(predicates::str::contains("1,7.5000,0.0000,7.5000,false"))
+        .stdout(predicates::str::contains("2,4.0000,0.0000,4.0000,false"))
+        .stdout(predicates::str::contains("3,1.0000,0.0000,1.0000,false"));
+
+    // Glob pattern
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!("{}/0[12].csv", shards));
+    cmd.assert()
+        .code(4)
+        .stdout(predicates::str::contains("2,0.0000,4.0000,4.0000,false"))
+        .stdout(predicates::str::contains("3,").not());
+
+    // The manifest processes the withdrawal before the deposit
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg("--manifest").arg(format!(
+        "{}/tests/resources/shards.manifest",
+        env!("CARGO_MANIFEST_DIR")
+    ));
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains(
+            "PROCESSOR ERROR: Invalid withdrawal transaction 3.",
+        ))
+        .stdout(predicates::str::contains("1,10.0000,0.0000,10.0000,false"));
+
+    Ok(())
+}
+
+#[test]
+fn test_compressed_input() -> Result<(), Box<dyn std::error::Error>> {
+    for file in ["disputes_example.csv.gz", "disputes_example.csv.zst"] {
+        let mut cmd = Command::cargo_bin("toy_processor")?;
+        cmd.arg(format!(
+            "{}/tests/resources/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            file
+        ));
+        cmd.assert()
+            .success()
+            .stdout(predicates::str::contains("1,10.0000,5.5000,15.5000,true"))
+            .stdout(predicates::str::contains("2,7.2500,0.0000,7.2500,false"))
+            .stdout(predicates::str::contains("3,0.0000,0.0000,0.0000,true"));
+    }
+
+    // A truncated file is read up to the broken part
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!(
+        "{}/tests/resources/truncated_example.csv.gz",
+        env!("CARGO_MANIFEST_DIR")
+    ));
+    cmd.assert().code(3).stderr(predicates::str::contains(
+        "Error when trying to read from CSV:",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_column_mapping() -> Result<(), Box<dyn std::error::Error>> {
+    let resources: String = format!("{}/tests/resources", env!("CARGO_MANIFEST_DIR"));
+
+    // Columns are read by header name, unknown columns are ignored
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!("{}/mapped_columns.csv", resources));
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("1,8.0000,5.5000,13.5000,false"))
+        .stdout(predicates::str::contains("2,7.2500,0.0000,7.2500,false"));
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!("{}/aliased_columns.csv", resources))
+        .arg("--column-aliases")
+        .arg(format!("{}/column_aliases.csv", resources));
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("1,6.0000,0.0000,6.0000,false"));
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!("{}/missing_columns.csv", resources));
+    cmd.assert()
+        .code(2)
+        .stderr(predicates::str::contains(
+            "PROCESSOR ERROR: Header has no client column.",
+        ))
+        .stderr(predicates::str::contains(
+            "ABORTED: An input file has an invalid header.",
+        ))
+        .stdout(predicates::str::is_empty());
+
+    // Without a header the first row is a transaction
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!("{}/headerless_example.csv", resources))
+        .arg("--headerless");
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("1,10.0000,5.5000,15.5000,true"))
+        .stdout(predicates::str::contains("3,0.0000,0.0000,0.0000,true"));
+
+    Ok(())
+}
+
+#[test]
+fn test_amount_validation() -> Result<(), Box<dyn std::error::Error>> {
+    let input: String = format!(
+        "{}/tests/resources/amounts_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    );
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(&input);
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains(
+            "Missing amount for deposit transaction 2.",
+        ))
+        .stderr(predicates::str::contains(
+            "Amount 1.00001 has more than 4 decimal places.",
+        ))
+        .stderr(predicates::str::contains(
+            "Amount NaN is not a finite number.",
+        ))
+        .stderr(predicates::str::contains(
+            "Amount 1e2 uses exponent notation.",
+        ))
+        .stderr(predicates::str::contains(
+            "Unexpected amount 10.0 for dispute transaction 1.",
+        ))
+        .stdout(predicates::str::contains("1,7.5000,0.0000,7.5000,false"));
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(&input).arg("--on-unexpected-amount").arg("ignore");
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains(
+            "WARNING: Amount 10.0 of dispute transaction 1 is ignored.",
+        ))
+        .stdout(predicates::str::contains("1,-2.5000,10.0000,7.5000,false"));
+
+    Ok(())
+}
+
+#[test]
+fn test_error_budget() -> Result<(), Box<dyn std::error::Error>> {
+    let input: String = format!(
+        "{}/tests/resources/example_with_errors.csv",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let output: String = format!("{}/budget_accounts.csv", env!("CARGO_TARGET_TMPDIR"));
+    let _ = std::fs::remove_file(&output);
+
+    // Stops at the first rejected row, before the second one
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(&input)
+        .arg("--on-error")
+        .arg("abort")
+        .arg("--output")
+        .arg(&output);
+    cmd.assert()
+        .code(5)
+        .stderr(predicates::str::contains(
+            "ABORTED: A row was rejected with --on-error abort.",
+        ))
+        .stderr(predicates::str::contains("transaction 6").not());
+    assert!(!std::path::Path::new(&output).exists());
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(&input).arg("--max-errors").arg("1");
+    cmd.assert()
+        .code(6)
+        .stdout(predicates::str::is_empty())
+        .stderr(predicates::str::contains(
+            "ABORTED: More than 1 row(s) were rejected.",
+        ));
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(&input).arg("--max-error-rate").arg("0.2");
+    cmd.assert()
+        .code(7)
+        .stdout(predicates::str::is_empty())
+        .stderr(predicates::str::contains(
+            "ABORTED: More than 0.2 of the rows were rejected.",
+        ));
+
+    // Within budget
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(&input)
+        .arg("--max-errors")
+        .arg("2")
+        .arg("--max-error-rate")
+        .arg("0.5");
+    cmd.assert()
+        .code(4)
+        .stdout(predicates::str::contains("1,1.5000,0.0000,1.5000,false"));
+
+    Ok(())
+}
+
+#[test]
+fn test_exit_codes() -> Result<(), Box<dyn std::error::Error>> {
+    let resources: String = format!("{}/tests/resources", env!("CARGO_MANIFEST_DIR"));
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!("{}/disputes_example.csv", resources));
+    cmd.assert().code(0);
+
+    // Usage errors
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!("{}/disputes_example.csv", resources))
+        .arg("--as-of-row")
+        .arg("1")
+        .arg("--as-of-timestamp")
+        .arg("1");
+    cmd.assert().code(2);
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg("--on-error").arg("retry");
+    cmd.assert().code(2);
+
+    // I/O errors
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!("{}/missing.csv", resources));
+    cmd.assert().code(3);
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!("{}/disputes_example.csv", resources))
+        .arg("--output")
+        .arg(format!("{}/missing/accounts.csv", resources));
+    cmd.assert().code(3);
+
+    // Processed with rejects
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!("{}/example_with_errors.csv", resources));
+    cmd.assert().code(4);
+
+    Ok(())
+}
+
+#[test]
+fn test_atomic_groups() -> Result<(), Box<dyn std::error::Error>> {
+    let resources: String = format!("{}/tests/resources", env!("CARGO_MANIFEST_DIR"));
+
+    // The second file is rolled back as a whole, client 3 is never created
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!("{}/settlement_ok.csv", resources))
+        .arg(format!("{}/settlement_bad.csv", resources))
+        .arg("--atomic")
+        .arg("file");
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains(format!(
+            "ROLLED BACK: {}/settlement_bad.csv: 3 row(s) undone after 1 rejected row(s).",
+            resources
+        )))
+        .stdout(predicates::str::contains("1,10.0000,0.0000,10.0000,false"))
+        .stdout(predicates::str::contains("2,5.0000,0.0000,5.0000,false"))
+        .stdout(predicates::str::contains("3,").not());
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!("{}/batches_example.csv", resources))
+        .arg("--atomic")
+        .arg("batch");
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains(format!(
+            "ROLLED BACK: batch b2 of {}/batches_example.csv: 3 row(s) undone after 1 rejected row(s).",
+            resources
+        )))
+        .stdout(predicates::str::contains("1,-3.0000,10.0000,7.0000,false"))
+        .stdout(predicates::str::contains("2,").not())
+        .stdout(predicates::str::contains("3,2.0000,0.0000,2.0000,false"));
+
+    // A file with a rolled back batch can be sent again, only its accepted
+    // rows are skipped
+    let dedup_state: String = format!("{}/batches_dedup_state.csv", env!("CARGO_TARGET_TMPDIR"));
+    let _ = std::fs::remove_file(&dedup_state);
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!("{}/batches_example.csv", resources))
+        .arg("--atomic")
+        .arg("batch")
+        .arg("--dedup-state")
+        .arg(&dedup_state);
+    cmd.assert().code(4);
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!("{}/batches_example.csv", resources))
+        .arg("--atomic")
+        .arg("batch")
+        .arg("--dedup-state")
+        .arg(&dedup_state)
+        .arg("--summary");
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains(format!(
+            "ROLLED BACK: batch b2 of {}/batches_example.csv",
+            resources
+        )))
+        .stderr(predicates::str::contains(
+            "SUMMARY: 7 row(s) read, 2 rejected, 4 duplicate row(s) and 0 duplicate file(s) skipped.",
+        ));
+
+    // Rolled back rows raise no alerts and don't count toward row deadlines
+    let alerts: String = format!("{}/rolled_back_alerts.csv", env!("CARGO_TARGET_TMPDIR"));
+    let synthetic_events: String = format!(
+        "{}/rolled_back_synthetic_events.csv",
+        env!("CARGO_TARGET_TMPDIR")
+    );
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!("{}/batches_example.csv", resources))
+        .arg("--atomic")
+        .arg("batch")
+        .arg("--rules")
+        .arg(format!("{}/rules.csv", resources))
+        .arg("--alerts")
+        .arg(&alerts)
+        .arg("--dispute-deadline")
+        .arg("1")
+        .arg("--synthetic-events")
+        .arg(&synthetic_events);
+    cmd.assert().code(4).stderr(predicates::str::contains(
+        "PENDING DISPUTE: Transaction 1 of client 1 is still open, its deadline passes at row 5.",
+    ));
+    assert_eq!(
+        "row,timestamp,client,tx,rule,action\n",
+        std::fs::read_to_string(&alerts)?
+    );
+    assert_eq!(
+        "row,type,client,tx,timestamp\n",
+        std::fs::read_to_string(&synthetic_events)?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_reversals() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(format!(
+        "{}/tests/resources/reversals_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .arg("--verify");
+
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains(
+            "Transaction 2 was reversed and can't be disputed.",
+        ))
+        .stderr(predicates::str::contains(
+            "Transaction 6 isn't registered for client 2.",
+        ))
+        .stderr(predicates::str::contains("Verification passed."))
+        .stdout(predicates::str::contains("1,-2.0000,0.0000,-2.0000,false"))
+        .stdout(predicates::str::contains("2,3.0000,0.0000,3.0000,false"));
+
+    Ok(())
+}
+
+#[test]
+fn test_authorizations() -> Result<(), Box<dyn std::error::Error>> {
+    let file: String = format!(
+        "{}/tests/resources/authorizations_example.csv",
+        env!("CARGO_MANIFEST_DIR")
+    );
+
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(&file).arg("--verify");
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains(
+            "Authorization 5 is already captured/voided.",
+        ))
+        .stderr(predicates::str::contains(
+            "Invalid authorize transaction 7. Available amount is smaller than authorized amount.",
+        ))
+        .stderr(predicates::str::contains(
+            "Transaction with ID: 6 cannot have negative or 0 amount.",
+        ))
+        .stderr(predicates::str::contains("Verification passed."))
+        .stdout(predicates::str::contains("1,8.5000,0.0000,8.5000,false"))
+        .stdout(predicates::str::contains("2,7.0000,3.0000,10.0000,false"));
+
+    // Authorization 6 expires before it's captured
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(&file).arg("--authorization-expiry").arg("3");
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains(
+            "EXPIRED AUTHORIZATION: Transaction 6 of client 2 got an automatic void at row 11.",
+        ))
+        .stdout(predicates::str::contains("2,10.0000,0.0000,10.0000,false"));
+
+    // Authorizations whose expiry hasn't passed at the end of the input stay
+    // open
+    let mut cmd = Command::cargo_bin("toy_processor")?;
+    cmd.arg(&file).arg("--authorization-expiry").arg("100");
+    cmd.assert()
+        .code(4)
+        .stderr(predicates::str::contains("EXPIRED AUTHORIZATION").not())
+        .stderr(predicates::str::contains(
+            "PENDING AUTHORIZATION: Transaction 6 of client 2 is still open, its deadline passes at row 108.",
+        ))
+        .stdout(predicates::str::contains("2,7.0000,3.0000,10.0000,false"));
+
+    Ok(())
+}